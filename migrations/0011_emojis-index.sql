@@ -0,0 +1,5 @@
+BEGIN;
+
+CREATE INDEX emojis_guild_id_datetime ON emojis (guild_id, datetime);
+
+COMMIT;
@@ -0,0 +1,6 @@
+BEGIN;
+
+ALTER TABLE settings
+    ADD COLUMN emojis_dedupe BOOL NOT NULL DEFAULT FALSE;
+
+COMMIT;
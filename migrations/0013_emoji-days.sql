@@ -0,0 +1,16 @@
+BEGIN;
+
+ALTER TABLE settings
+    ADD COLUMN emojis_enabled   BOOL    NOT NULL DEFAULT TRUE,
+    ADD COLUMN emojis_retention INTEGER NOT NULL DEFAULT 90;
+
+CREATE TABLE emoji_days (
+    guild_id VARCHAR(24) NOT NULL,
+    day      BIGINT      NOT NULL,
+    emoji_id VARCHAR(24) NOT NULL,
+    reaction BOOLEAN     NOT NULL,
+    count    BIGINT      NOT NULL,
+    PRIMARY KEY (guild_id, day, emoji_id, reaction)
+);
+
+COMMIT;
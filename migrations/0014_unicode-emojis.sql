@@ -0,0 +1,14 @@
+BEGIN;
+
+ALTER TABLE emojis
+    ALTER COLUMN emoji_id TYPE VARCHAR(64),
+    ADD COLUMN emoji_type VARCHAR(8) NOT NULL DEFAULT 'custom';
+
+-- a custom and a unicode emoji could share an id, so the type is part of the key
+ALTER TABLE emoji_days
+    ALTER COLUMN emoji_id TYPE VARCHAR(64),
+    ADD COLUMN emoji_type VARCHAR(8) NOT NULL DEFAULT 'custom',
+    DROP CONSTRAINT emoji_days_pkey,
+    ADD PRIMARY KEY (guild_id, day, emoji_id, emoji_type, reaction);
+
+COMMIT;
@@ -0,0 +1,29 @@
+BEGIN;
+
+CREATE TABLE tracked_phrases (
+    id        SERIAL      PRIMARY KEY,
+    guild_id  VARCHAR(24) NOT NULL,
+    pattern   TEXT        NOT NULL,
+    regex     BOOLEAN     NOT NULL DEFAULT FALSE,
+    cooldown  INTEGER     NOT NULL DEFAULT 30,
+    template  TEXT        NOT NULL,
+    count     BIGINT      NOT NULL DEFAULT 0,
+    last_seen BIGINT
+);
+
+CREATE INDEX tracked_phrases_guild_id ON tracked_phrases (guild_id);
+
+-- carry over servers that had vtrack turned on
+INSERT INTO tracked_phrases (guild_id, pattern, regex, cooldown, template)
+SELECT
+    guild_id,
+    '\b[Vv][Oo][Rr][Ee]',
+    TRUE,
+    30,
+    E'{user} has broken the silence and said the cursed word.\nThis server has gone {time} since the last infraction.'
+FROM settings WHERE vtrack;
+
+ALTER TABLE settings
+    DROP COLUMN vtrack;
+
+COMMIT;
@@ -0,0 +1,15 @@
+BEGIN;
+
+CREATE TABLE phrase_infractions (
+    id        SERIAL      PRIMARY KEY,
+    phrase_id INTEGER     NOT NULL REFERENCES tracked_phrases (id) ON DELETE CASCADE,
+    guild_id  VARCHAR(24) NOT NULL,
+    member_id VARCHAR(24) NOT NULL,
+    datetime  BIGINT      NOT NULL,
+    -- seconds since the phrase was said before, or null the first time
+    gap       BIGINT
+);
+
+CREATE INDEX phrase_infractions_phrase_id ON phrase_infractions (phrase_id);
+
+COMMIT;
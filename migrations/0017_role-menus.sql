@@ -0,0 +1,23 @@
+BEGIN;
+
+CREATE TABLE role_menus (
+    id         SERIAL      PRIMARY KEY,
+    guild_id   VARCHAR(24) NOT NULL,
+    channel_id VARCHAR(24) NOT NULL,
+    message_id VARCHAR(24) NOT NULL UNIQUE,
+    title      TEXT        NOT NULL,
+    -- members can only have one role from an exclusive menu
+    exclusive  BOOLEAN     NOT NULL DEFAULT FALSE,
+    -- reaction menus are toggled with reactions, everything else with buttons
+    reactions  BOOLEAN     NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE role_menu_entries (
+    menu_id  INTEGER     NOT NULL REFERENCES role_menus (id) ON DELETE CASCADE,
+    role_id  VARCHAR(24) NOT NULL,
+    emoji    VARCHAR(128),
+    position INTEGER     NOT NULL,
+    PRIMARY KEY (menu_id, role_id)
+);
+
+COMMIT;
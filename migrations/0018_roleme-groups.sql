@@ -0,0 +1,19 @@
+BEGIN;
+
+CREATE TABLE roleme_groups (
+    id               SERIAL       PRIMARY KEY,
+    guild_id         VARCHAR(24)  NOT NULL,
+    name             VARCHAR(100) NOT NULL,
+    -- picking a role from an exclusive group takes away the others
+    exclusive        BOOLEAN      NOT NULL DEFAULT FALSE,
+    -- how many roles from the group a member can have, or null for no limit
+    max_picks        INTEGER,
+    -- a role members need before they can pick from the group
+    required_role_id VARCHAR(24),
+    UNIQUE (guild_id, name)
+);
+
+ALTER TABLE roleme_roles
+    ADD COLUMN group_id INTEGER REFERENCES roleme_groups (id) ON DELETE SET NULL;
+
+COMMIT;
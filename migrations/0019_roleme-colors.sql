@@ -0,0 +1,12 @@
+BEGIN;
+
+-- colors members can pick with roleme color. when a server has any, members can only pick these.
+-- colors are stored like roleme_roles.color, as lowercase #rrggbb.
+CREATE TABLE roleme_palette (
+    guild_id VARCHAR(24)  NOT NULL,
+    color    VARCHAR(7)   NOT NULL,
+    name     VARCHAR(100),
+    PRIMARY KEY (guild_id, color)
+);
+
+COMMIT;
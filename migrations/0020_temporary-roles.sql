@@ -0,0 +1,14 @@
+BEGIN;
+
+-- roles that are taken away again once expires_at, in unix seconds, has passed
+CREATE TABLE temporary_roles (
+    guild_id   VARCHAR(24) NOT NULL,
+    member_id  VARCHAR(24) NOT NULL,
+    role_id    VARCHAR(24) NOT NULL,
+    expires_at BIGINT      NOT NULL,
+    PRIMARY KEY (guild_id, member_id, role_id)
+);
+
+CREATE INDEX temporary_roles_expires_at ON temporary_roles (expires_at);
+
+COMMIT;
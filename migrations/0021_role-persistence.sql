@@ -0,0 +1,21 @@
+BEGIN;
+
+ALTER TABLE settings
+    ADD COLUMN roleme_persist BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- roles other than roleme roles that are given back to members who rejoin
+CREATE TABLE persistent_roles (
+    guild_id VARCHAR(24) NOT NULL,
+    role_id  VARCHAR(24) NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+-- roles members had when they left, given back if they rejoin
+CREATE TABLE departed_roles (
+    guild_id  VARCHAR(24) NOT NULL,
+    member_id VARCHAR(24) NOT NULL,
+    role_id   VARCHAR(24) NOT NULL,
+    PRIMARY KEY (guild_id, member_id, role_id)
+);
+
+COMMIT;
@@ -0,0 +1,16 @@
+BEGIN;
+
+-- who can use a command, a category of commands, or every command, and where. command is a
+-- command name, "category:" followed by a category name, or "all". categories are prefixed
+-- because some of them share a name with a command.
+CREATE TABLE command_rules (
+    id        SERIAL      PRIMARY KEY,
+    guild_id  VARCHAR(24) NOT NULL,
+    command   VARCHAR(32) NOT NULL,
+    kind      VARCHAR(8)  NOT NULL CHECK (kind IN ('channel', 'role', 'user')),
+    target_id VARCHAR(24) NOT NULL,
+    allow     BOOLEAN     NOT NULL,
+    UNIQUE (guild_id, command, kind, target_id)
+);
+
+COMMIT;
@@ -0,0 +1,446 @@
+use crate::{
+    emoji::{self, ParsedEmoji},
+    model::{GenericError, MessageContext},
+};
+use chrono::Duration;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    future::Future,
+    mem,
+    pin::Pin,
+};
+use twilight_model::{channel::GuildChannel, guild::Role, id::ChannelId, user::User};
+
+lazy_static! {
+    static ref CHANNEL_ID: Regex = Regex::new(r"^(?:<#)?(?P<id>\d{15,21})>?$").unwrap();
+    static ref DURATION: Regex = Regex::new(
+        r"^(?:(?P<w>\d{1,4})w)?(?:(?P<d>\d{1,5})d)?(?:(?P<h>\d{1,6})h)?(?:(?P<m>\d{1,7})m)?(?:(?P<s>\d{1,9})s)?$"
+    )
+    .unwrap();
+}
+
+/// A problem with the arguments given to a command. The dispatcher shows these to the user.
+#[derive(Debug)]
+pub struct ArgError(pub String);
+
+impl Display for ArgError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ArgError {}
+
+impl ArgError {
+    pub fn invalid(message: impl Into<String>) -> GenericError {
+        Box::new(Self(message.into()))
+    }
+}
+
+pub type ArgFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, GenericError>> + Send + 'a>>;
+
+/// Something a command argument can be parsed into. Arguments that don't parse are an
+/// [`ArgError`], while failures looking them up are passed along as they are.
+pub trait Arg: Sized {
+    /// What the argument is called in error messages.
+    const NAME: &'static str;
+
+    fn parse<'a>(context: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self>;
+}
+
+impl Arg for String {
+    const NAME: &'static str = "word";
+
+    fn parse<'a>(_: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move { Ok(arg.to_string()) })
+    }
+}
+
+macro_rules! int_arg {
+    ($($int:ty),*) => {
+        $(
+            impl Arg for $int {
+                const NAME: &'static str = "number";
+
+                fn parse<'a>(_: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+                    Box::pin(async move {
+                        arg.parse()
+                            .map_err(|_| ArgError::invalid(format!("`{}` isn't a number.", arg)))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+int_arg!(i32, i64, u16, u64, usize);
+
+/// A member of the guild, by mention, id, username#discriminator, or name.
+#[derive(Debug)]
+pub struct UserArg(pub User);
+
+impl Arg for UserArg {
+    const NAME: &'static str = "member";
+
+    fn parse<'a>(context: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move {
+            match context.find_member(arg).await? {
+                Some(user) => Ok(Self(user)),
+                None => Err(ArgError::invalid(format!(
+                    "Couldn't find the member `{}`.",
+                    arg
+                ))),
+            }
+        })
+    }
+}
+
+/// A role in the guild, found with [`MessageContext::find_role`].
+#[derive(Debug)]
+pub struct RoleArg(pub Role);
+
+impl Arg for RoleArg {
+    const NAME: &'static str = "role";
+
+    fn parse<'a>(context: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move {
+            let roles = context.guild_roles();
+
+            context
+                .find_role(arg, &roles)
+                .await?
+                .map(Self)
+                .ok_or_else(|| ArgError::invalid(format!("Couldn't find the role `{}`.", arg)))
+        })
+    }
+}
+
+/// A channel in the guild, by mention, id, or name, ignoring case and a leading #.
+#[derive(Debug)]
+pub struct ChannelArg(pub GuildChannel);
+
+impl Arg for ChannelArg {
+    const NAME: &'static str = "channel";
+
+    fn parse<'a>(context: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move {
+            let guild_id = context.message.guild_id;
+            let channel_ids = guild_id
+                .and_then(|guild_id| context.cache().guild_channels(guild_id))
+                .map(|channels| channels.iter().copied().collect::<Vec<ChannelId>>())
+                .unwrap_or_default();
+
+            let by_id = CHANNEL_ID
+                .captures(arg)
+                .and_then(|caps| caps["id"].parse().ok())
+                .and_then(ChannelId::new)
+                .filter(|id| channel_ids.contains(id));
+
+            let name = arg.trim_start_matches('#').to_lowercase();
+
+            let channel = match by_id {
+                Some(id) => context
+                    .cache()
+                    .guild_channel(id)
+                    .map(|channel| channel.resource().clone()),
+                None => channel_ids
+                    .into_iter()
+                    .filter_map(|id| context.cache().guild_channel(id))
+                    .find(|channel| channel.name().to_lowercase() == name)
+                    .map(|channel| channel.resource().clone()),
+            };
+
+            channel
+                .map(Self)
+                .ok_or_else(|| ArgError::invalid(format!("Couldn't find the channel `{}`.", arg)))
+        })
+    }
+}
+
+/// A custom or unicode emoji.
+#[derive(Debug)]
+pub struct EmojiArg(pub ParsedEmoji);
+
+impl Arg for EmojiArg {
+    const NAME: &'static str = "emoji";
+
+    fn parse<'a>(_: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move {
+            emoji::parse_one(arg)
+                .map(Self)
+                .ok_or_else(|| ArgError::invalid(format!("`{}` isn't an emoji.", arg)))
+        })
+    }
+}
+
+/// A length of time like `1h30m`, in weeks, days, hours, minutes, and seconds. A plain number is
+/// read as minutes.
+#[derive(Debug)]
+pub struct DurationArg(pub Duration);
+
+impl Arg for DurationArg {
+    const NAME: &'static str = "duration";
+
+    fn parse<'a>(_: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move {
+            duration(arg).map(Self).ok_or_else(|| {
+                ArgError::invalid(format!(
+                    "`{}` isn't a duration. Try something like `90`, `45m`, or `1d12h`.",
+                    arg
+                ))
+            })
+        })
+    }
+}
+
+pub fn duration(arg: &str) -> Option<Duration> {
+    let arg = arg.trim().to_lowercase();
+
+    if let Ok(minutes) = arg.parse::<u32>() {
+        return Some(Duration::minutes(minutes as i64));
+    }
+
+    let caps = DURATION.captures(&arg).filter(|_| !arg.is_empty())?;
+    let unit = |name: &str| -> i64 {
+        caps.name(name)
+            .and_then(|value| value.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+
+    Some(
+        Duration::weeks(unit("w"))
+            + Duration::days(unit("d"))
+            + Duration::hours(unit("h"))
+            + Duration::minutes(unit("m"))
+            + Duration::seconds(unit("s")),
+    )
+}
+
+/// A color like `#ff8800`, `ff8800`, or `#f80`.
+pub struct ColorArg(pub u32);
+
+impl Arg for ColorArg {
+    const NAME: &'static str = "color";
+
+    fn parse<'a>(_: &'a MessageContext, arg: &'a str) -> ArgFuture<'a, Self> {
+        Box::pin(async move {
+            color(arg).map(Self).ok_or_else(|| {
+                ArgError::invalid(format!(
+                    "`{}` isn't a color. Try something like `#ff8800`.",
+                    arg
+                ))
+            })
+        })
+    }
+}
+
+pub fn color(arg: &str) -> Option<u32> {
+    let hex = arg.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // the short form doubles every digit, so #f80 is #ff8800
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+/// Split a message into words, keeping "quoted words" together. This never fails: a quote that
+/// isn't closed runs to the end of the message, and apostrophes are just letters.
+pub fn tokenize(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut started = false;
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // only quotes can be escaped, so patterns like \b keep their backslash
+            '\\' if matches!(chars.peek(), Some('"' | '“' | '”')) => {
+                token.extend(chars.next());
+                started = true;
+            }
+            '"' | '“' | '”' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    tokens.push(mem::take(&mut token));
+                    started = false;
+                }
+            }
+            c => {
+                token.push(c);
+                started = true;
+            }
+        }
+    }
+
+    if started {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+impl MessageContext {
+    /// Take the next argument as the name of a subcommand, if there is one.
+    pub fn subcommand(&mut self) -> Option<String> {
+        if self.args.is_empty() {
+            return None;
+        }
+
+        Some(self.args.remove(0))
+    }
+
+    /// Take the next argument as a subcommand, but only if it's one of `names`. Commands that
+    /// read anything else as an argument use this, so the argument is still there for them.
+    pub fn subcommand_of(&mut self, names: &[&'static str]) -> Option<&'static str> {
+        let first = self.args.first()?;
+        let name = names.iter().copied().find(|name| name == first)?;
+
+        self.args.remove(0);
+
+        Some(name)
+    }
+
+    /// Parse the next argument, if there is one.
+    pub async fn optional_arg<T: Arg>(&mut self) -> Result<Option<T>, GenericError> {
+        if self.args.is_empty() {
+            return Ok(None);
+        }
+
+        let arg = self.args.remove(0);
+
+        T::parse(self, &arg).await.map(Some)
+    }
+
+    /// Parse everything that's left as one argument, like a name with spaces in it.
+    pub async fn rest<T: Arg>(&mut self) -> Result<T, GenericError> {
+        match self.optional_rest().await? {
+            Some(rest) => Ok(rest),
+            None => Err(ArgError::invalid(format!("Missing a {}.", T::NAME))),
+        }
+    }
+
+    /// Parse everything that's left as one argument, if anything is left.
+    pub async fn optional_rest<T: Arg>(&mut self) -> Result<Option<T>, GenericError> {
+        let rest = mem::take(&mut self.args).join(" ");
+
+        if rest.is_empty() {
+            return Ok(None);
+        }
+
+        T::parse(self, &rest).await.map(Some)
+    }
+
+    /// Whether a flag like `--all` was given anywhere. Flags are taken out of the arguments, so
+    /// they should be read before anything else.
+    pub fn flag(&mut self, name: &str) -> bool {
+        let before = self.args.len();
+        self.args.retain(|arg| arg != name);
+
+        self.args.len() != before
+    }
+
+    /// Parse the value after an option like `--days 7`, if it was given anywhere. Like flags,
+    /// options are taken out of the arguments.
+    pub async fn option<T: Arg>(&mut self, name: &str) -> Result<Option<T>, GenericError> {
+        let index = match self.args.iter().position(|arg| arg == name) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        if index + 1 >= self.args.len() {
+            return Err(ArgError::invalid(format!(
+                "`{}` needs a {}.",
+                name,
+                T::NAME
+            )));
+        }
+
+        let value = self.args.remove(index + 1);
+        self.args.remove(index);
+
+        T::parse(self, &value).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(content: &str) -> Vec<&str> {
+        content.split(' ').collect()
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(
+            tokenize("katze emojis  --days 7"),
+            words("katze emojis --days 7")
+        );
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(
+            tokenize(r#"katze shuffle "two words" three"#),
+            vec!["katze", "shuffle", "two words", "three"]
+        );
+        assert_eq!(tokenize(r#"a "" b"#), vec!["a", "", "b"]);
+        assert_eq!(tokenize("“smart quotes” too"), vec!["smart quotes", "too"]);
+        assert_eq!(tokenize(r#"a"b c"d"#), vec!["ab cd"]);
+    }
+
+    #[test]
+    fn lenient() {
+        assert_eq!(tokenize("don't stop"), vec!["don't", "stop"]);
+        assert_eq!(
+            tokenize(r#"he said "hi there"#),
+            vec!["he", "said", "hi there"]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(tokenize(r#"say \"hi\""#), vec!["say", "\"hi\""]);
+        assert_eq!(tokenize(r"\bv\w+"), vec![r"\bv\w+"]);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration("90"), Some(Duration::minutes(90)));
+        assert_eq!(duration("45m"), Some(Duration::minutes(45)));
+        assert_eq!(duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(duration("1w2d"), Some(Duration::days(9)));
+        assert_eq!(duration("10S"), Some(Duration::seconds(10)));
+        assert_eq!(duration(""), None);
+        assert_eq!(duration("m"), None);
+        assert_eq!(duration("30m1h"), None);
+        assert_eq!(duration("soon"), None);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(color("#ff8800"), Some(0xff8800));
+        assert_eq!(color("FF8800"), Some(0xff8800));
+        assert_eq!(color("#f80"), Some(0xff8800));
+        assert_eq!(color("#ff880"), None);
+        assert_eq!(color("+fff"), None);
+        assert_eq!(color("orange"), None);
+    }
+}
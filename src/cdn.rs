@@ -0,0 +1,67 @@
+use twilight_model::{
+    channel::message::sticker::StickerId,
+    id::{EmojiId, GuildId, UserId},
+};
+
+const CDN: &str = "https://cdn.discordapp.com";
+
+pub const SIZES: [u16; 9] = [16, 32, 64, 128, 256, 512, 1024, 2048, 4096];
+
+pub fn is_animated(hash: &str) -> bool {
+    hash.starts_with("a_")
+}
+
+// animated hashes are served as gifs by default, but every hash can be requested as a still image
+pub fn formats(hash: &str) -> &'static [&'static str] {
+    if is_animated(hash) {
+        &["gif", "png", "jpg", "webp"]
+    } else {
+        &["png", "jpg", "webp"]
+    }
+}
+
+pub fn avatar(user_id: UserId, hash: &str, format: &str, size: u16) -> String {
+    format!(
+        "{}/avatars/{}/{}.{}?size={}",
+        CDN, user_id, hash, format, size
+    )
+}
+
+pub fn banner(user_id: UserId, hash: &str, format: &str, size: u16) -> String {
+    format!(
+        "{}/banners/{}/{}.{}?size={}",
+        CDN, user_id, hash, format, size
+    )
+}
+
+pub fn default_avatar(discriminator: u16) -> String {
+    format!("{}/embed/avatars/{}.png", CDN, discriminator % 5)
+}
+
+pub fn emoji(emoji_id: EmojiId, format: &str) -> String {
+    format!("{}/emojis/{}.{}?v=1", CDN, emoji_id, format)
+}
+
+pub fn icon(guild_id: GuildId, hash: &str, format: &str, size: u16) -> String {
+    format!(
+        "{}/icons/{}/{}.{}?size={}",
+        CDN, guild_id, hash, format, size
+    )
+}
+
+pub fn member_avatar(
+    guild_id: GuildId,
+    user_id: UserId,
+    hash: &str,
+    format: &str,
+    size: u16,
+) -> String {
+    format!(
+        "{}/guilds/{}/users/{}/avatars/{}.{}?size={}",
+        CDN, guild_id, user_id, hash, format, size
+    )
+}
+
+pub fn sticker(sticker_id: StickerId) -> String {
+    format!("{}/stickers/{}.png", CDN, sticker_id)
+}
@@ -0,0 +1,336 @@
+use image::{imageops, png::PngEncoder, ColorType, ImageResult, Rgba, RgbaImage};
+use std::cmp;
+
+const BACKGROUND: Rgba<u8> = Rgba([54, 57, 63, 255]);
+const GRID: Rgba<u8> = Rgba([79, 84, 92, 255]);
+const TEXT: Rgba<u8> = Rgba([220, 221, 222, 255]);
+const BAR: Rgba<u8> = Rgba([88, 101, 242, 255]);
+const PALETTE: [Rgba<u8>; 8] = [
+    Rgba([88, 101, 242, 255]),
+    Rgba([237, 66, 69, 255]),
+    Rgba([87, 242, 135, 255]),
+    Rgba([254, 231, 92, 255]),
+    Rgba([235, 69, 158, 255]),
+    Rgba([69, 221, 235, 255]),
+    Rgba([255, 155, 62, 255]),
+    Rgba([185, 130, 255, 255]),
+];
+
+pub const ICON: u32 = 32;
+pub const MAX_SERIES: usize = PALETTE.len();
+
+const PADDING: u32 = 16;
+const PLOT_HEIGHT: u32 = 240;
+const PLOT_WIDTH: u32 = 560;
+const SLOT: u32 = 48;
+const SWATCH: u32 = 48;
+const SWATCHES_PER_ROW: usize = 5;
+const SCALE: u32 = 2;
+
+// 3x5 bitmaps of the digits 0-9, one row per byte, most significant of the low three bits first
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b011, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+const TEXT_HEIGHT: u32 = 5 * SCALE;
+
+fn fill(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    let (image_width, image_height) = image.dimensions();
+
+    for px in x..cmp::min(x + width, image_width) {
+        for py in y..cmp::min(y + height, image_height) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+fn text_width(number: i64) -> u32 {
+    number.to_string().len() as u32 * 4 * SCALE - SCALE
+}
+
+fn text(image: &mut RgbaImage, number: i64, x: u32, y: u32) {
+    for (index, digit) in number.to_string().bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let left = x + index as u32 * 4 * SCALE;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    let px = left + column * SCALE;
+                    let py = y + row as u32 * SCALE;
+                    fill(image, px, py, SCALE, SCALE, TEXT);
+                }
+            }
+        }
+    }
+}
+
+// a thick line between two points, drawn by stepping along the longer axis
+fn line(image: &mut RgbaImage, from: (i64, i64), to: (i64, i64), color: Rgba<u8>) {
+    let steps = cmp::max((to.0 - from.0).abs(), (to.1 - from.1).abs()).max(1);
+
+    for step in 0..=steps {
+        let x = from.0 + (to.0 - from.0) * step / steps;
+        let y = from.1 + (to.1 - from.1) * step / steps;
+        fill(
+            image,
+            (x - 1).max(0) as u32,
+            (y - 1).max(0) as u32,
+            3,
+            3,
+            color,
+        );
+    }
+}
+
+fn icon(image: &mut RgbaImage, icon: &RgbaImage, x: u32, y: u32) {
+    // center icons that aren't square
+    let (width, height) = icon.dimensions();
+    imageops::overlay(image, icon, x + (ICON - width) / 2, y + (ICON - height) / 2);
+}
+
+// horizontal grid lines at every quarter of the plot, labeled with their value
+fn grid(image: &mut RgbaImage, left: u32, width: u32, top: u32, max: i64) {
+    for quarter in 0..=4 {
+        let y = top + PLOT_HEIGHT - PLOT_HEIGHT * quarter / 4;
+        fill(image, left, y, width, 1, GRID);
+
+        let value = max * quarter as i64 / 4;
+        let x = left.saturating_sub(text_width(value) + SCALE * 2);
+        text(image, value, x, y.saturating_sub(TEXT_HEIGHT / 2));
+    }
+}
+
+fn gutter(max: i64) -> u32 {
+    PADDING + text_width(max) + SCALE * 2
+}
+
+/// A bar chart with one bar per icon, labeled with its value.
+pub fn bars(values: &[(RgbaImage, i64)]) -> RgbaImage {
+    let max = values
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let left = gutter(max);
+    let top = PADDING + TEXT_HEIGHT + SCALE * 2;
+    let width = left + values.len() as u32 * SLOT + PADDING;
+    let height = top + PLOT_HEIGHT + SCALE * 4 + ICON + PADDING;
+
+    let mut image = RgbaImage::from_pixel(width, height, BACKGROUND);
+    grid(&mut image, left, values.len() as u32 * SLOT, top, max);
+
+    for (index, (label, value)) in values.iter().enumerate() {
+        let x = left + index as u32 * SLOT + (SLOT - ICON) / 2;
+        let bar = (PLOT_HEIGHT as i64 * value / max) as u32;
+
+        fill(&mut image, x, top + PLOT_HEIGHT - bar, ICON, bar, BAR);
+
+        let label_x = x + ICON / 2 - cmp::min(text_width(*value) / 2, x + ICON / 2);
+        let label_y = top + PLOT_HEIGHT - bar - TEXT_HEIGHT - SCALE * 2;
+        text(&mut image, *value, label_x, label_y);
+
+        icon(&mut image, label, x, top + PLOT_HEIGHT + SCALE * 4);
+    }
+
+    image
+}
+
+/// A line chart with one line per icon, with a legend matching icons to colors. Every series
+/// must have the same number of values.
+pub fn lines(series: &[(RgbaImage, Vec<i64>)]) -> RgbaImage {
+    let max = series
+        .iter()
+        .flat_map(|(_, values)| values.iter().copied())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let points = series
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or(0);
+
+    let left = gutter(max);
+    let top = PADDING + TEXT_HEIGHT;
+    let legend = left + PLOT_WIDTH + PADDING;
+    let width = legend + 16 + SCALE * 4 + ICON + PADDING;
+    let height = cmp::max(
+        top + PLOT_HEIGHT + PADDING,
+        PADDING * 2 + series.len() as u32 * (ICON + SCALE * 4),
+    );
+
+    let mut image = RgbaImage::from_pixel(width, height, BACKGROUND);
+    grid(&mut image, left, PLOT_WIDTH, top, max);
+
+    let x = |index: usize| -> i64 {
+        if points < 2 {
+            (left + PLOT_WIDTH / 2) as i64
+        } else {
+            (left + PLOT_WIDTH * index as u32 / (points as u32 - 1)) as i64
+        }
+    };
+
+    let y = |value: i64| -> i64 { (top + PLOT_HEIGHT) as i64 - PLOT_HEIGHT as i64 * value / max };
+
+    for (index, (label, values)) in series.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+
+        for (point, value) in values.iter().enumerate() {
+            let from = (x(point), y(*value));
+            let to = match values.get(point + 1) {
+                Some(next) => (x(point + 1), y(*next)),
+                None => from,
+            };
+
+            line(&mut image, from, to, color);
+        }
+
+        let row = PADDING + index as u32 * (ICON + SCALE * 4);
+        fill(&mut image, legend, row + (ICON - 16) / 2, 16, 16, color);
+        icon(&mut image, label, legend + 16 + SCALE * 4, row);
+    }
+
+    image
+}
+
+/// A grid of color swatches, numbered from 1 underneath.
+pub fn swatches(colors: &[Rgba<u8>]) -> RgbaImage {
+    let columns = colors.len().clamp(1, SWATCHES_PER_ROW) as u32;
+    let rows = colors.len().div_ceil(SWATCHES_PER_ROW).max(1) as u32;
+    let cell = SWATCH + SCALE * 2 + TEXT_HEIGHT + PADDING;
+
+    let width = PADDING + columns * (SWATCH + PADDING);
+    let height = PADDING + rows * cell;
+
+    let mut image = RgbaImage::from_pixel(width, height, BACKGROUND);
+
+    for (index, color) in colors.iter().enumerate() {
+        let x = PADDING + (index % SWATCHES_PER_ROW) as u32 * (SWATCH + PADDING);
+        let y = PADDING + (index / SWATCHES_PER_ROW) as u32 * cell;
+
+        fill(&mut image, x, y, SWATCH, SWATCH, *color);
+
+        let number = index as i64 + 1;
+        text(
+            &mut image,
+            number,
+            x + (SWATCH - text_width(number)) / 2,
+            y + SWATCH + SCALE * 2,
+        );
+    }
+
+    image
+}
+
+pub fn encode(image: &RgbaImage) -> ImageResult<Vec<u8>> {
+    let mut encoded = Vec::new();
+    let (width, height) = image.dimensions();
+
+    PngEncoder::new(&mut encoded).encode(image.as_raw(), width, height, ColorType::Rgba8)?;
+
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icons(count: usize) -> Vec<RgbaImage> {
+        (0..count)
+            .map(|_| RgbaImage::from_pixel(ICON, ICON, Rgba([255, 255, 255, 255])))
+            .collect()
+    }
+
+    #[test]
+    fn digit_widths() {
+        // each digit is three pixels wide with a one pixel gap between them, all scaled
+        assert_eq!(text_width(0), 3 * SCALE);
+        assert_eq!(text_width(7), 3 * SCALE);
+        assert_eq!(text_width(10), 7 * SCALE);
+        assert_eq!(text_width(12345), 19 * SCALE);
+    }
+
+    #[test]
+    fn digits_drawn() {
+        let mut image = RgbaImage::from_pixel(text_width(80), TEXT_HEIGHT, BACKGROUND);
+        text(&mut image, 80, 0, 0);
+
+        // the top left of an 8 is lit, and the middle of a 0 isn't
+        assert_eq!(*image.get_pixel(0, 0), TEXT);
+        assert_eq!(*image.get_pixel(4 * SCALE + SCALE, 2 * SCALE), BACKGROUND);
+    }
+
+    #[test]
+    fn bar_layout() {
+        let values = icons(3).into_iter().zip(vec![4, 2, 0]).collect::<Vec<_>>();
+        let image = bars(&values);
+
+        let left = gutter(4);
+        let top = PADDING + TEXT_HEIGHT + SCALE * 2;
+        assert_eq!(image.width(), left + 3 * SLOT + PADDING);
+
+        // the tallest bar reaches the top of the plot, and half the value reaches half as far
+        let x = left + (SLOT - ICON) / 2 + ICON / 2;
+        assert_eq!(*image.get_pixel(x, top + 1), BAR);
+        assert_eq!(*image.get_pixel(x + SLOT, top + PLOT_HEIGHT / 2 + 1), BAR);
+        assert_eq!(
+            *image.get_pixel(x + SLOT, top + PLOT_HEIGHT / 2 - 1),
+            BACKGROUND
+        );
+    }
+
+    #[test]
+    fn empty_bars() {
+        let image = bars(&[]);
+        assert_eq!(image.width(), gutter(1) + PADDING);
+    }
+
+    #[test]
+    fn swatch_layout() {
+        let colors = vec![PALETTE[1]; SWATCHES_PER_ROW + 1];
+        let image = swatches(&colors);
+        let cell = SWATCH + SCALE * 2 + TEXT_HEIGHT + PADDING;
+
+        assert_eq!(
+            image.dimensions(),
+            (
+                PADDING + SWATCHES_PER_ROW as u32 * (SWATCH + PADDING),
+                PADDING + 2 * cell
+            )
+        );
+
+        // the sixth swatch wraps to the start of the second row
+        assert_eq!(*image.get_pixel(PADDING, PADDING + cell), PALETTE[1]);
+        assert_eq!(
+            *image.get_pixel(PADDING * 2 + SWATCH, PADDING + cell),
+            BACKGROUND
+        );
+    }
+
+    #[test]
+    fn swatch_numbers_fit() {
+        // numbers are centered under each swatch with SWATCH - text_width, so they must never be
+        // wider than a swatch
+        assert!(text_width(99999) <= SWATCH);
+
+        let colors = vec![PALETTE[0]; 100];
+        let image = swatches(&colors);
+        assert_eq!(
+            image.height(),
+            PADDING + 20 * (SWATCH + SCALE * 2 + TEXT_HEIGHT + PADDING)
+        );
+    }
+}
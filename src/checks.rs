@@ -1,16 +1,54 @@
-use crate::model::{GenericError, MessageContext};
+use crate::{
+    commands::perms,
+    model::{BaseContext, GenericError, MessageContext},
+    table::CommandRule,
+};
 use std::{
+    cmp::Reverse,
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
 };
-use twilight_model::{guild::Permissions, id::RoleId};
+use twilight_model::{
+    channel::{permission_overwrite::PermissionOverwrite, Channel, ChannelType, GuildChannel},
+    guild::{Permissions, Role},
+    id::{ChannelId, GuildId, RoleId, UserId},
+};
 use twilight_util::permission_calculator::PermissionCalculator;
 
+/// Permissions that let a member moderate or take over a server. Roles with any of these can't be
+/// handed out to whoever asks.
+pub const DANGEROUS: Permissions = Permissions::from_bits_truncate(
+    Permissions::ADMINISTRATOR.bits()
+        | Permissions::BAN_MEMBERS.bits()
+        | Permissions::DEAFEN_MEMBERS.bits()
+        | Permissions::KICK_MEMBERS.bits()
+        | Permissions::MANAGE_CHANNELS.bits()
+        | Permissions::MANAGE_EMOJIS_AND_STICKERS.bits()
+        | Permissions::MANAGE_GUILD.bits()
+        | Permissions::MANAGE_MESSAGES.bits()
+        | Permissions::MANAGE_NICKNAMES.bits()
+        | Permissions::MANAGE_ROLES.bits()
+        | Permissions::MANAGE_THREADS.bits()
+        | Permissions::MANAGE_WEBHOOKS.bits()
+        | Permissions::MENTION_EVERYONE.bits()
+        | Permissions::MOVE_MEMBERS.bits()
+        | Permissions::MUTE_MEMBERS.bits()
+        | Permissions::VIEW_AUDIT_LOG.bits(),
+);
+
 #[derive(Debug)]
 pub enum CheckError {
+    AuthorHierarchy(String),
+    BotHierarchy(String),
+    BotMissingPermissions(Permissions),
+    ChannelRule(String),
+    DangerousRole(String, Permissions),
+    ManagedRole(String),
+    MemberRule(String),
     MissingPermissions(Permissions),
     NoGuild,
     NotOwner,
+    NotStaff,
 }
 
 impl Error for CheckError {}
@@ -18,59 +56,519 @@ impl Error for CheckError {}
 impl Display for CheckError {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
+            Self::AuthorHierarchy(name) => write!(
+                f,
+                "You can't manage `{}`, because it isn't below your highest role.",
+                name
+            ),
+            Self::BotHierarchy(name) => write!(
+                f,
+                "I can't manage `{}`, because it isn't below my highest role.",
+                name
+            ),
+            Self::BotMissingPermissions(permissions) => {
+                write!(f, "I am missing permissions: {:?}.", permissions)
+            }
+            Self::ChannelRule(command) => {
+                write!(f, "`{}` can't be used in this channel.", command)
+            }
+            Self::DangerousRole(name, permissions) => write!(
+                f,
+                "`{}` can't be handed out, because it has dangerous permissions: {:?}.",
+                name, permissions
+            ),
+            Self::ManagedRole(name) => write!(
+                f,
+                "`{}` is managed by Discord or an integration, so it can't be handed out.",
+                name
+            ),
+            Self::MemberRule(command) => write!(f, "You aren't allowed to use `{}`.", command),
             Self::MissingPermissions(permissions) => {
                 write!(f, "You are missing permissions: {:?}.", permissions)
             }
             Self::NoGuild => f.write_str("No guild id"),
             Self::NotOwner => write!(f, "You are not the owner."),
+            Self::NotStaff => write!(f, "You are not staff."),
         }
     }
 }
 
 pub fn is_owner(context: &MessageContext) -> Result<(), GenericError> {
-    if dotenv::var("OWNER")?.parse::<u64>()? == context.message.author.id.get() {
+    if context.staff().is_owner(context.message.author.id) {
         Ok(())
     } else {
         Err(CheckError::NotOwner.into())
     }
 }
 
-// there is no in memory cache guild roles, so we just pretend the only guild roles are the ones
-// the member has.
+/// Make sure the author is one of the bot's staff, or an owner.
+pub fn is_staff(context: &MessageContext) -> Result<(), GenericError> {
+    if context.staff().is_staff(context.message.author.id) {
+        Ok(())
+    } else {
+        Err(CheckError::NotStaff.into())
+    }
+}
+
+// the permissions that belong to the whole server rather than to a channel. overwrites can't
+// change them, so they always come from the server's. in a channel, manage roles means "manage
+// permissions" for that channel, which is no good for handing out roles.
+const GUILD_ONLY: Permissions = Permissions::from_bits_truncate(
+    Permissions::ADMINISTRATOR.bits()
+        | Permissions::BAN_MEMBERS.bits()
+        | Permissions::CHANGE_NICKNAME.bits()
+        | Permissions::KICK_MEMBERS.bits()
+        | Permissions::MANAGE_EMOJIS_AND_STICKERS.bits()
+        | Permissions::MANAGE_GUILD.bits()
+        | Permissions::MANAGE_NICKNAMES.bits()
+        | Permissions::MANAGE_ROLES.bits()
+        | Permissions::VIEW_AUDIT_LOG.bits()
+        | Permissions::VIEW_GUILD_INSIGHTS.bits(),
+);
+
+// the roles a member has. the cache doesn't have every member, so this asks discord when it
+// doesn't.
+async fn roles_of(
+    context: &BaseContext,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Vec<RoleId>, GenericError> {
+    if let Some(member) = context.cache().member(guild_id, user_id) {
+        return Ok(member.roles().to_vec());
+    }
+
+    let member = context
+        .http()
+        .guild_member(guild_id, user_id)
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    Ok(member.roles)
+}
+
+// the roles a member has, from the message for its author
+async fn member_roles(
+    context: &MessageContext,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Vec<RoleId>, GenericError> {
+    if user_id == context.message.author.id {
+        if let Some(member) = &context.message.member {
+            return Ok(member.roles.clone());
+        }
+    }
+
+    roles_of(context.base(), guild_id, user_id).await
+}
+
+// the permissions of every role in the guild, from the cache if it has all of them
+async fn role_permissions(
+    context: &MessageContext,
+    guild_id: GuildId,
+    role_ids: &[RoleId],
+) -> Result<Vec<(RoleId, Permissions)>, GenericError> {
+    let cached = role_ids
+        .iter()
+        .filter_map(|id| {
+            context
+                .cache()
+                .role(*id)
+                .map(|role| (role.id, role.permissions))
+        })
+        .collect::<Vec<_>>();
+
+    if cached.len() == role_ids.len() {
+        return Ok(cached);
+    }
+
+    let roles = context
+        .http()
+        .roles(guild_id)
+        .exec()
+        .await?
+        .model()
+        .await?
+        .into_iter()
+        .map(|role| (role.id, role.permissions))
+        .collect();
+
+    Ok(roles)
+}
+
+async fn owner_id(context: &BaseContext, guild_id: GuildId) -> Result<UserId, GenericError> {
+    if let Some(guild) = context.cache().guild(guild_id) {
+        return Ok(guild.owner_id());
+    }
+
+    let guild = context.http().guild(guild_id).exec().await?.model().await?;
+
+    Ok(guild.owner_id)
+}
+
+async fn guild_channel(
+    context: &MessageContext,
+    channel_id: ChannelId,
+) -> Result<Option<GuildChannel>, GenericError> {
+    if let Some(channel) = context.cache().guild_channel(channel_id) {
+        return Ok(Some(channel.resource().clone()));
+    }
+
+    let channel = context
+        .http()
+        .channel(channel_id)
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    match channel {
+        Channel::Guild(channel) => Ok(Some(channel)),
+        _ => Ok(None),
+    }
+}
+
+// the kind of a channel, and its overwrites. threads don't have overwrites of their own, so they
+// get the ones of the channel they're in.
+async fn overwrites(
+    context: &MessageContext,
+    channel_id: ChannelId,
+) -> Result<Option<(ChannelType, Vec<PermissionOverwrite>)>, GenericError> {
+    let channel = match guild_channel(context, channel_id).await? {
+        Some(channel) => channel,
+        None => return Ok(None),
+    };
+
+    let parent_id = match &channel {
+        GuildChannel::NewsThread(thread) => thread.parent_id,
+        GuildChannel::PrivateThread(thread) => thread.parent_id,
+        GuildChannel::PublicThread(thread) => thread.parent_id,
+        GuildChannel::Category(category) => {
+            return Ok(Some((
+                channel.kind(),
+                category.permission_overwrites.clone(),
+            )))
+        }
+        GuildChannel::Text(text) => {
+            return Ok(Some((channel.kind(), text.permission_overwrites.clone())))
+        }
+        GuildChannel::Voice(voice) | GuildChannel::Stage(voice) => {
+            return Ok(Some((channel.kind(), voice.permission_overwrites.clone())))
+        }
+    };
+
+    let parent = match parent_id {
+        Some(parent_id) => guild_channel(context, parent_id).await?,
+        None => None,
+    };
+
+    let overwrites = match parent {
+        Some(GuildChannel::Text(text)) => text.permission_overwrites,
+        Some(GuildChannel::Voice(voice)) | Some(GuildChannel::Stage(voice)) => {
+            voice.permission_overwrites
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(Some((ChannelType::GuildText, overwrites)))
+}
+
+/// The permissions a member has in a channel, after its overwrites. Anything the cache doesn't
+/// have is fetched from discord.
+pub async fn permissions_in(
+    context: &MessageContext,
+    guild_id: GuildId,
+    user_id: UserId,
+    channel_id: ChannelId,
+) -> Result<Permissions, GenericError> {
+    // the everyone role has the same id as the guild
+    let everyone_id = RoleId(guild_id.0);
+
+    let mut role_ids = member_roles(context, guild_id, user_id).await?;
+    role_ids.push(everyone_id);
+
+    let roles = role_permissions(context, guild_id, &role_ids).await?;
+
+    let everyone = roles
+        .iter()
+        .find(|(id, _)| *id == everyone_id)
+        .map(|(_, permissions)| *permissions)
+        .unwrap_or_else(Permissions::empty);
+
+    let member_roles = roles
+        .into_iter()
+        .filter(|(id, _)| *id != everyone_id && role_ids.contains(id))
+        .collect::<Vec<_>>();
+
+    let calculator = PermissionCalculator::new(guild_id, user_id, everyone, &member_roles)
+        .owner_id(owner_id(context.base(), guild_id).await?);
+
+    let root = calculator.root();
+
+    let permissions = match overwrites(context, channel_id).await? {
+        Some((kind, overwrites)) => {
+            (calculator.in_channel(kind, &overwrites) - GUILD_ONLY) | (root & GUILD_ONLY)
+        }
+        None => root,
+    };
+
+    Ok(permissions)
+}
+
+/// Make sure the author has permissions in the channel the message was sent in.
 pub async fn has_permission(
     context: &MessageContext,
     permissions: Permissions,
 ) -> Result<(), GenericError> {
+    let guild_id = context.message.guild_id.ok_or(CheckError::NoGuild)?;
+
+    let held = permissions_in(
+        context,
+        guild_id,
+        context.message.author.id,
+        context.message.channel_id,
+    )
+    .await?;
+
+    if !held.contains(permissions) {
+        return Err(CheckError::MissingPermissions(permissions - held).into());
+    }
+
+    Ok(())
+}
+
+async fn bot_id(context: &BaseContext) -> Result<UserId, GenericError> {
+    if let Some(user) = context.cache().current_user() {
+        return Ok(user.id);
+    }
+
+    let user = context.http().current_user().exec().await?.model().await?;
+
+    Ok(user.id)
+}
+
+/// Make sure the bot has the permissions it needs for a command in a channel, so it can say so
+/// instead of failing halfway through.
+pub async fn bot_has_permission_in(
+    context: &MessageContext,
+    channel_id: ChannelId,
+    permissions: Permissions,
+) -> Result<(), GenericError> {
+    let guild_id = context.message.guild_id.ok_or(CheckError::NoGuild)?;
+    let bot_id = bot_id(context.base()).await?;
+
+    let held = permissions_in(context, guild_id, bot_id, channel_id).await?;
+
+    if !held.contains(permissions) {
+        return Err(CheckError::BotMissingPermissions(permissions - held).into());
+    }
+
+    Ok(())
+}
+
+/// Make sure the bot has the permissions it needs for a command in the channel the message was
+/// sent in.
+pub async fn bot_has_permission(
+    context: &MessageContext,
+    permissions: Permissions,
+) -> Result<(), GenericError> {
+    bot_has_permission_in(context, context.message.channel_id, permissions).await
+}
+
+// roles are ordered by position, and roles in the same position by age, oldest first
+fn rank(role: &Role) -> (i64, Reverse<u64>) {
+    (role.position, Reverse(role.id.get()))
+}
+
+// whether a member's highest role is above a role
+async fn above(
+    context: &BaseContext,
+    guild_id: GuildId,
+    user_id: UserId,
+    role: &Role,
+) -> Result<bool, GenericError> {
+    let role_ids = roles_of(context, guild_id, user_id).await?;
+
+    let highest = role_ids
+        .into_iter()
+        .filter_map(|id| context.cache().role(id).map(|role| rank(&role)))
+        .max();
+
+    Ok(highest.is_some_and(|highest| highest > rank(role)))
+}
+
+/// Make sure the bot can give a role to members of a guild and take it away.
+pub async fn bot_can_manage(
+    context: &BaseContext,
+    guild_id: GuildId,
+    role: &Role,
+) -> Result<(), GenericError> {
+    // the everyone role has the same id as the guild
+    if role.managed || role.id.0 == guild_id.0 {
+        return Err(CheckError::ManagedRole(role.name.clone()).into());
+    }
+
+    let bot_id = bot_id(context).await?;
+
+    if !above(context, guild_id, bot_id, role).await? {
+        return Err(CheckError::BotHierarchy(role.name.clone()).into());
+    }
+
+    Ok(())
+}
+
+/// Make sure the bot can give a role to members and take it away.
+pub async fn can_manage(context: &MessageContext, role: &Role) -> Result<(), GenericError> {
+    let guild_id = context.message.guild_id.ok_or(CheckError::NoGuild)?;
+
+    bot_can_manage(context.base(), guild_id, role).await
+}
+
+/// Make sure the author can hand out a role: the bot can manage it, and so can the author, unless
+/// they own the server.
+pub async fn can_delegate(context: &MessageContext, role: &Role) -> Result<(), GenericError> {
+    let guild_id = context.message.guild_id.ok_or(CheckError::NoGuild)?;
+    let author_id = context.message.author.id;
+
+    can_manage(context, role).await?;
+
+    let owner = owner_id(context.base(), guild_id).await? == author_id;
+
+    if !owner && !above(context.base(), guild_id, author_id, role).await? {
+        return Err(CheckError::AuthorHierarchy(role.name.clone()).into());
+    }
+
+    Ok(())
+}
+
+/// Make sure a role is safe to let members give themselves: it has no dangerous permissions, and
+/// the author can hand it out.
+pub async fn can_hand_out(context: &MessageContext, role: &Role) -> Result<(), GenericError> {
+    if role.permissions.intersects(DANGEROUS) {
+        let permissions = role.permissions & DANGEROUS;
+
+        return Err(CheckError::DangerousRole(role.name.clone(), permissions).into());
+    }
+
+    can_delegate(context, role).await
+}
+
+// what the rules in one scope say about the author. a rule for the author beats rules for their
+// roles, and like discord's own permissions, one allowed role is enough. once anyone is allowed,
+// everyone else isn't.
+fn member_verdict(rules: &[&CommandRule], user_id: &str, role_ids: &[String]) -> Option<bool> {
+    if let Some(rule) = rules
+        .iter()
+        .find(|rule| rule.kind == "user" && rule.target_id == user_id)
+    {
+        return Some(rule.allow);
+    }
+
+    let held = rules
+        .iter()
+        .filter(|rule| rule.kind == "role" && role_ids.contains(&rule.target_id))
+        .collect::<Vec<_>>();
+
+    if held.iter().any(|rule| rule.allow) {
+        Some(true)
+    } else if !held.is_empty()
+        || rules
+            .iter()
+            .any(|rule| rule.kind != "channel" && rule.allow)
+    {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// what the rules in one scope say about the channel. once any channel is allowed, the others
+// aren't.
+fn channel_verdict(rules: &[&CommandRule], channel_id: &str) -> Option<bool> {
+    if let Some(rule) = rules
+        .iter()
+        .find(|rule| rule.kind == "channel" && rule.target_id == channel_id)
+    {
+        return Some(rule.allow);
+    }
+
+    if rules
+        .iter()
+        .any(|rule| rule.kind == "channel" && rule.allow)
+    {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Apply the server's rules for who can use a command, and where. Rules for the command beat
+/// rules for its category, which beat rules for every command. Administrators aren't affected, so
+/// they can't lock themselves out.
+pub async fn command_rules(context: &MessageContext, command: &str) -> Result<(), GenericError> {
     let guild_id = match context.message.guild_id {
         Some(guild_id) => guild_id,
-        None => return Err(Box::new(CheckError::NoGuild)),
+        None => return Ok(()),
     };
 
-    if let (Some(member), Some(guild), Some(everyone_role)) = (
-        context.cache().member(guild_id, context.message.author.id),
-        context.cache().guild(guild_id),
-        context.cache().role(RoleId(guild_id.0)),
-    ) {
-        let roles = member
-            .roles()
+    let category = perms::category(command).unwrap_or_default();
+
+    let rules = sqlx::query_as!(
+        CommandRule,
+        "SELECT id, command, kind, target_id, allow FROM command_rules WHERE
+        (guild_id = $1 AND command IN ($2, $3, 'all'));",
+        guild_id.to_string(),
+        command,
+        category,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    if rules.is_empty()
+        || has_permission(context, Permissions::ADMINISTRATOR)
+            .await
+            .is_ok()
+    {
+        return Ok(());
+    }
+
+    // everyone has the everyone role, which has the same id as the guild
+    let mut role_ids = context
+        .author_roles()
+        .into_iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>();
+    role_ids.push(guild_id.to_string());
+
+    let user_id = context.message.author.id.to_string();
+    let channel_id = context.message.channel_id.to_string();
+
+    let scopes = [command, category.as_str(), "all"];
+    let scoped = |scope: &str| {
+        rules
             .iter()
-            .filter_map(|id| context.cache().role(*id))
-            .map(|role| (role.id, role.permissions))
-            .collect::<Vec<_>>();
-
-        let calculator = PermissionCalculator::new(
-            guild_id,
-            context.message.author.id,
-            everyone_role.permissions,
-            roles.as_slice(),
-        )
-        .owner_id(guild.owner_id())
-        .root();
-
-        if calculator.contains(permissions) {
-            return Ok(());
-        }
+            .filter(|rule| rule.command == scope)
+            .collect::<Vec<&CommandRule>>()
+    };
+
+    let member = scopes
+        .iter()
+        .find_map(|scope| member_verdict(&scoped(scope), &user_id, &role_ids))
+        .unwrap_or(true);
+
+    if !member {
+        return Err(CheckError::MemberRule(command.to_string()).into());
+    }
+
+    let channel = scopes
+        .iter()
+        .find_map(|scope| channel_verdict(&scoped(scope), &channel_id))
+        .unwrap_or(true);
+
+    if !channel {
+        return Err(CheckError::ChannelRule(command.to_string()).into());
     }
 
-    Err(CheckError::MissingPermissions(permissions).into())
+    Ok(())
 }
@@ -0,0 +1,1371 @@
+use crate::{
+    args::{ArgError, UserArg},
+    cdn, chart, checks,
+    commands::steal::{self, Slots},
+    emoji::{self, ParsedEmoji},
+    error::KatzeError,
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
+    search,
+    table::{
+        DayCount, EmojiCount, EmojiDayCount, EmojiUsage, MemberCount, Setting, UnicodeEmojiCount,
+    },
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use futures_util::{future, stream, StreamExt};
+use hyper::{
+    body::{self, Body},
+    Request, Uri,
+};
+use image::{imageops, RgbaImage};
+use serde_json::{json, Value};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt::Write,
+    io::{Cursor, Read, Write as IoWrite},
+    mem,
+};
+use twilight_http::request::{AttachmentFile, AuditLogReason};
+use twilight_model::{
+    guild::{Permissions, PremiumTier},
+    id::{EmojiId, GuildId},
+};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+const CHART_BARS: usize = 10;
+const CHART_LINES: usize = 5;
+const CHART_MAX_BARS: usize = 25;
+const DAY: i64 = 86400;
+const DEFAULT_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 3650;
+const MAX_NAMES: usize = 40;
+const MIN_RETENTION: i32 = 7;
+const PACK_CONCURRENCY: usize = 8;
+const PACK_LIMIT: u64 = 25 * 1024 * 1024;
+const PACK_MANIFEST: &str = "manifest.json";
+const PACK_MANIFEST_LIMIT: u64 = 1024 * 1024;
+const PACK_PROGRESS: usize = 5;
+// a server can have at most 500 emojis, which can be at most 256 KiB each
+const PACK_UNPACKED_LIMIT: u64 = 500 * steal::EMOJI_LIMIT as u64;
+const PER_LINE: usize = 5;
+const TOP_USERS: i64 = 10;
+const TREND_DAYS: i64 = 14;
+const UNUSED_DAYS: i64 = 30;
+
+const USAGE: &str = "USAGE: katze emojis [<emoji>] [--days <N> | --all] [--user <User>] \
+                     [--reactions-only | --messages-only] [--least]";
+const CHART_USAGE: &str = "USAGE: katze emojis chart [--line] [--top <N>] [--days <N> | --all] \
+                           [--user <User>] [--reactions-only | --messages-only]";
+const DEDUPE_USAGE: &str = "USAGE: katze emojis dedupe [on | off]";
+const IMPORT_USAGE: &str = "USAGE: katze emojis import, with an emoji pack attached or replied to";
+const PURGE_USAGE: &str = "USAGE: katze emojis purge [--user <User>]";
+const RETENTION_USAGE: &str = "USAGE: katze emojis retention [<days>], from 7 to 3650 days";
+const TRACKING_USAGE: &str = "USAGE: katze emojis tracking [on | off]";
+const UNUSED_USAGE: &str = "USAGE: katze emojis unused [<days>] [--max <uses>] [--delete]";
+
+#[derive(Debug)]
+struct GuildEmoji {
+    name: String,
+    formatted: String,
+    parsed: ParsedEmoji,
+    managed: bool,
+}
+
+#[derive(Debug)]
+struct Filter {
+    since: i64,
+    days: Option<i64>,
+    member: Option<(String, String)>,
+    reaction: Option<bool>,
+    least: bool,
+    query: Vec<String>,
+}
+
+impl Filter {
+    fn describe(&self) -> String {
+        let mut description = match self.days {
+            Some(1) => String::from("in the last day"),
+            Some(days) => format!("in the last {} days", days),
+            None => String::from("of all time"),
+        };
+
+        if let Some((_, name)) = &self.member {
+            description.push_str(&format!(" by {}", name));
+        }
+
+        match self.reaction {
+            Some(true) => description.push_str(", reactions only"),
+            Some(false) => description.push_str(", messages only"),
+            None => {}
+        }
+
+        description
+    }
+
+    fn member_id(&self) -> Option<String> {
+        self.member.as_ref().map(|(id, _)| id.clone())
+    }
+}
+
+// the emojis that currently exist in the guild, keyed by id
+fn guild_emojis(context: &MessageContext, guild_id: GuildId) -> HashMap<EmojiId, GuildEmoji> {
+    context
+        .cache()
+        .guild_emojis(guild_id)
+        .map(|emojis| {
+            emojis
+                .iter()
+                .filter_map(|id| context.cache().emoji(*id))
+                .map(|emoji| {
+                    let parsed = ParsedEmoji::Custom {
+                        animated: emoji.animated(),
+                        name: emoji.name().to_string(),
+                        id: emoji.id(),
+                    };
+
+                    let guild_emoji = GuildEmoji {
+                        name: emoji.name().to_string(),
+                        formatted: parsed.to_string(),
+                        parsed,
+                        managed: emoji.managed(),
+                    };
+
+                    (emoji.id(), guild_emoji)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// usage counts of the given emojis, most used first. emojis that were never used are left out
+async fn counts(
+    context: &MessageContext,
+    guild_id: GuildId,
+    emojis: &HashMap<EmojiId, GuildEmoji>,
+    since: i64,
+    member_id: Option<String>,
+    reaction: Option<bool>,
+) -> Result<Vec<(EmojiId, i64)>, GenericError> {
+    let ids = emojis
+        .keys()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>();
+
+    let counts = sqlx::query_as!(
+        EmojiCount,
+        "SELECT
+            emoji_id AS \"emoji_id!: _\",
+            SUM(count)::BIGINT AS \"count!\"
+        FROM (
+            SELECT emoji_id, COUNT(*) AS count FROM emojis WHERE
+            (guild_id = $1 AND datetime >= $2 AND emoji_id = ANY($3)
+                AND ($4::TEXT IS NULL OR member_id = $4)
+                AND ($5::BOOLEAN IS NULL OR reaction = $5))
+            GROUP BY emoji_id
+            UNION ALL
+            SELECT emoji_id, SUM(count) AS count FROM emoji_days WHERE
+            (guild_id = $1 AND day * 86400 >= $2 AND emoji_id = ANY($3)
+                AND $4::TEXT IS NULL
+                AND ($5::BOOLEAN IS NULL OR reaction = $5))
+            GROUP BY emoji_id
+        ) AS counts
+        GROUP BY emoji_id
+        ORDER BY 2 DESC, emoji_id DESC;",
+        guild_id.to_string(),
+        since,
+        &ids,
+        member_id,
+        reaction,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    Ok(counts
+        .into_iter()
+        .map(|row| (row.emoji_id.0, row.count))
+        .collect())
+}
+
+// usage counts of unicode emojis, most used first
+async fn unicode_counts(
+    context: &MessageContext,
+    guild_id: GuildId,
+    since: i64,
+    member_id: Option<String>,
+    reaction: Option<bool>,
+) -> Result<Vec<(String, i64)>, GenericError> {
+    let counts = sqlx::query_as!(
+        UnicodeEmojiCount,
+        "SELECT
+            emoji_id AS \"emoji!\",
+            SUM(count)::BIGINT AS \"count!\"
+        FROM (
+            SELECT emoji_id, COUNT(*) AS count FROM emojis WHERE
+            (guild_id = $1 AND emoji_type = 'unicode' AND datetime >= $2
+                AND ($3::TEXT IS NULL OR member_id = $3)
+                AND ($4::BOOLEAN IS NULL OR reaction = $4))
+            GROUP BY emoji_id
+            UNION ALL
+            SELECT emoji_id, SUM(count) AS count FROM emoji_days WHERE
+            (guild_id = $1 AND emoji_type = 'unicode' AND day * 86400 >= $2
+                AND $3::TEXT IS NULL
+                AND ($4::BOOLEAN IS NULL OR reaction = $4))
+            GROUP BY emoji_id
+        ) AS counts
+        GROUP BY emoji_id
+        ORDER BY 2 DESC, emoji_id;",
+        guild_id.to_string(),
+        since,
+        member_id,
+        reaction,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    Ok(counts
+        .into_iter()
+        .map(|row| (row.emoji, row.count))
+        .collect())
+}
+
+// the options shared by the stats and the chart. mistakes in them are shown with the usage of
+// whichever command they were given to.
+async fn filter(context: &mut MessageContext, usage: &str) -> Result<Filter, GenericError> {
+    let least = context.flag("--least");
+    let reaction = match (
+        context.flag("--messages-only"),
+        context.flag("--reactions-only"),
+    ) {
+        (true, true) => {
+            return Err(ArgError::invalid(format!(
+                "Use only one of `--messages-only` and `--reactions-only`.\n{}",
+                usage
+            )))
+        }
+        (true, false) => Some(false),
+        (false, true) => Some(true),
+        (false, false) => None,
+    };
+
+    let all = context.flag("--all");
+    let days = match (all, context.option::<i64>("--days").await?) {
+        (true, Some(_)) => {
+            return Err(ArgError::invalid(format!(
+                "Use only one of `--all` and `--days`.\n{}",
+                usage
+            )))
+        }
+        (true, None) => None,
+        (false, Some(d)) if d > 0 && d <= MAX_DAYS => Some(d),
+        (false, Some(_)) => {
+            return Err(ArgError::invalid(format!(
+                "`--days` must be a number from 1 to {}.\n{}",
+                MAX_DAYS, usage
+            )))
+        }
+        (false, None) => Some(DEFAULT_DAYS),
+    };
+
+    let member = context
+        .option::<UserArg>("--user")
+        .await?
+        .map(|UserArg(user)| (user.id.to_string(), user.name));
+
+    let query = mem::take(&mut context.args);
+
+    let since = match days {
+        Some(days) => (Utc::now() - Duration::days(days)).timestamp(),
+        None => 0,
+    };
+
+    Ok(Filter {
+        since,
+        days,
+        member,
+        reaction,
+        least,
+        query,
+    })
+}
+
+async fn icon(context: &MessageContext, emoji_id: EmojiId) -> Result<RgbaImage, GenericError> {
+    let uri = Uri::try_from(cdn::emoji(emoji_id, "png"))?;
+    let request = Request::get(uri).body(Body::empty())?;
+    let mut response = context.hyper().request(request).await?;
+    let buffer = body::to_bytes(response.body_mut()).await?;
+
+    let image = image::load_from_memory(&buffer)?;
+    let image = image.resize(chart::ICON, chart::ICON, imageops::Triangle);
+
+    Ok(image.to_rgba8())
+}
+
+async fn chart(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    // the chart takes a couple options of its own
+    let line = context.flag("--line");
+    let top = match context.option::<usize>("--top").await? {
+        Some(t) if t == 0 || t > CHART_MAX_BARS => {
+            let reply = context.reply(CHART_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+        top => top,
+    };
+
+    let filter = filter(context, CHART_USAGE).await?;
+
+    if !filter.query.is_empty() {
+        let reply = context.reply(CHART_USAGE).await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let top = if line {
+        top.unwrap_or(CHART_LINES).min(chart::MAX_SERIES)
+    } else {
+        top.unwrap_or(CHART_BARS)
+    };
+
+    let emojis = guild_emojis(context, guild_id);
+
+    let counts = counts(
+        context,
+        guild_id,
+        &emojis,
+        filter.since,
+        filter.member_id(),
+        filter.reaction,
+    )
+    .await?
+    .into_iter()
+    .take(top)
+    .collect::<Vec<(EmojiId, i64)>>();
+
+    if counts.is_empty() {
+        let reply = context
+            .reply(format!("No emojis were used {}.", filter.describe()))
+            .await?;
+
+        return Ok(Response::Message(reply));
+    }
+
+    context
+        .http()
+        .create_typing_trigger(context.message.channel_id)
+        .exec()
+        .await?;
+
+    let icons = future::join_all(counts.iter().map(|(id, _)| icon(context, *id)))
+        .await
+        .into_iter()
+        .collect::<Result<Vec<RgbaImage>, GenericError>>()?;
+
+    let image = if line {
+        let ids = counts
+            .iter()
+            .map(|(id, _)| id.to_string())
+            .collect::<Vec<String>>();
+
+        let days = sqlx::query_as!(
+            EmojiDayCount,
+            "SELECT
+                emoji_id AS \"emoji_id!: _\",
+                day AS \"day!\",
+                SUM(count)::BIGINT AS \"count!\"
+            FROM (
+                SELECT emoji_id, datetime / 86400 AS day, COUNT(*) AS count FROM emojis WHERE
+                (guild_id = $1 AND datetime >= $2 AND emoji_id = ANY($3)
+                    AND ($4::TEXT IS NULL OR member_id = $4)
+                    AND ($5::BOOLEAN IS NULL OR reaction = $5))
+                GROUP BY 1, 2
+                UNION ALL
+                SELECT emoji_id, day, SUM(count) AS count FROM emoji_days WHERE
+                (guild_id = $1 AND day * 86400 >= $2 AND emoji_id = ANY($3)
+                    AND $4::TEXT IS NULL
+                    AND ($5::BOOLEAN IS NULL OR reaction = $5))
+                GROUP BY 1, 2
+            ) AS days
+            GROUP BY 1, 2
+            ORDER BY 2;",
+            guild_id.to_string(),
+            filter.since,
+            &ids,
+            filter.member_id(),
+            filter.reaction,
+        )
+        .fetch_all(context.postgres())
+        .await?;
+
+        // every series covers each day in the range, even days without any uses
+        let today = Utc::now().timestamp() / DAY;
+        let first = match filter.days {
+            Some(days) => today - days + 1,
+            None => days.first().map(|row| row.day).unwrap_or(today),
+        };
+
+        let series = counts
+            .iter()
+            .zip(icons)
+            .map(|((id, _), icon)| {
+                let mut values = vec![0; (today - first + 1) as usize];
+
+                for row in days.iter().filter(|row| row.emoji_id == *id) {
+                    if let Some(value) = values.get_mut((row.day - first) as usize) {
+                        *value = row.count;
+                    }
+                }
+
+                (icon, values)
+            })
+            .collect::<Vec<(RgbaImage, Vec<i64>)>>();
+
+        chart::lines(&series)
+    } else {
+        let bars = icons
+            .into_iter()
+            .zip(counts.iter().map(|(_, count)| *count))
+            .collect::<Vec<(RgbaImage, i64)>>();
+
+        chart::bars(&bars)
+    };
+
+    let encoded = chart::encode(&image)?;
+    let attachment = AttachmentFile::from_bytes("chart.png", encoded.as_ref());
+
+    let content = format!("**Top {} emojis {}**", counts.len(), filter.describe());
+
+    let reply = context
+        .http()
+        .create_message(context.message.channel_id)
+        .content(&content)?
+        .attach(&[attachment])
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn dedupe(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let enabled = match context.subcommand().as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => {
+            let reply = context.reply(DEDUPE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+        None => {
+            let setting = Setting::query(context.postgres().clone(), guild_id).await?;
+            let content = if setting.emojis_dedupe {
+                "Repeated emojis in a message are counted once."
+            } else {
+                "Every repeated emoji in a message is counted."
+            };
+
+            let reply = context.reply(content).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    sqlx::query!(
+        "UPDATE settings SET emojis_dedupe = $2 WHERE (guild_id = $1);",
+        guild_id.to_string(),
+        enabled,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.emojis().forget_settings(guild_id);
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn detail(
+    context: &MessageContext,
+    filter: &Filter,
+    emoji: &ParsedEmoji,
+) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let usage = sqlx::query_as!(
+        EmojiUsage,
+        "SELECT
+            COALESCE(SUM(count) FILTER (WHERE NOT reaction), 0)::BIGINT AS \"messages!\",
+            COALESCE(SUM(count) FILTER (WHERE reaction), 0)::BIGINT AS \"reactions!\"
+        FROM (
+            SELECT reaction, 1 AS count FROM emojis WHERE
+            (guild_id = $1 AND emoji_id = $2 AND datetime >= $3
+                AND ($4::TEXT IS NULL OR member_id = $4)
+                AND ($5::BOOLEAN IS NULL OR reaction = $5))
+            UNION ALL
+            SELECT reaction, count FROM emoji_days WHERE
+            (guild_id = $1 AND emoji_id = $2 AND day * 86400 >= $3
+                AND $4::TEXT IS NULL
+                AND ($5::BOOLEAN IS NULL OR reaction = $5))
+        ) AS uses;",
+        guild_id.to_string(),
+        emoji.key(),
+        filter.since,
+        filter.member_id(),
+        filter.reaction,
+    )
+    .fetch_one(context.postgres())
+    .await?;
+
+    let top_users = sqlx::query_as!(
+        MemberCount,
+        "SELECT
+            member_id AS \"member_id: _\",
+            COUNT(*) AS \"count!\"
+        FROM emojis WHERE
+        (guild_id = $1 AND emoji_id = $2 AND datetime >= $3
+            AND ($4::TEXT IS NULL OR member_id = $4)
+            AND ($5::BOOLEAN IS NULL OR reaction = $5))
+        GROUP BY member_id
+        ORDER BY 2 DESC, member_id
+        LIMIT $6;",
+        guild_id.to_string(),
+        emoji.key(),
+        filter.since,
+        filter.member_id(),
+        filter.reaction,
+        TOP_USERS,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    let trend = sqlx::query_as!(
+        DayCount,
+        "SELECT
+            day AS \"day!\",
+            SUM(count)::BIGINT AS \"count!\"
+        FROM (
+            SELECT datetime / 86400 AS day, 1 AS count FROM emojis WHERE
+            (guild_id = $1 AND emoji_id = $2 AND datetime >= $3
+                AND ($4::TEXT IS NULL OR member_id = $4)
+                AND ($5::BOOLEAN IS NULL OR reaction = $5))
+            UNION ALL
+            SELECT day, count FROM emoji_days WHERE
+            (guild_id = $1 AND emoji_id = $2 AND day * 86400 >= $3
+                AND $4::TEXT IS NULL
+                AND ($5::BOOLEAN IS NULL OR reaction = $5))
+        ) AS uses
+        GROUP BY 1
+        ORDER BY 1 DESC
+        LIMIT $6;",
+        guild_id.to_string(),
+        emoji.key(),
+        filter.since,
+        filter.member_id(),
+        filter.reaction,
+        TREND_DAYS,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    let mut content = format!(
+        "**Usage of {} {}**\nUsed **{}** time(s): **{}** in messages, **{}** as reactions.\n",
+        emoji,
+        filter.describe(),
+        usage.messages + usage.reactions,
+        usage.messages,
+        usage.reactions,
+    );
+
+    if !top_users.is_empty() {
+        content.push_str("\n**Top users**\n");
+
+        // rolled up usage doesn't say who it was, so the top users can come up short of the total
+        let retention = Setting::query(context.postgres().clone(), guild_id)
+            .await?
+            .emojis_retention as i64;
+
+        if filter.days.is_none_or(|days| days > retention) {
+            writeln!(
+                content,
+                "Usage older than {} days is kept as daily totals, so it isn't counted here.",
+                retention
+            )?;
+        }
+
+        for row in top_users.iter() {
+            let name = context
+                .cache()
+                .user(row.member_id.0)
+                .map(|user| format!("{}#{:04}", user.name, user.discriminator))
+                .unwrap_or_else(|| row.member_id.to_string());
+
+            writeln!(content, "`{}` {}", row.count, name)?;
+        }
+    }
+
+    if !trend.is_empty() {
+        content.push_str("\n**Daily trend**\n");
+
+        // scale the bars to the busiest day, oldest days first
+        let most = trend.iter().map(|row| row.count).max().unwrap_or(1);
+        for row in trend.iter().rev() {
+            let date = NaiveDateTime::from_timestamp(row.day * DAY, 0).date();
+            let bar = "█".repeat(((row.count * 20 + most - 1) / most) as usize);
+
+            writeln!(content, "`{}` `{:>4}` {}", date, row.count, bar)?;
+        }
+    }
+
+    let reply = context.reply(content).await?;
+
+    Ok(Response::Message(reply))
+}
+
+// the file name and image of one emoji in a pack
+async fn pack_image(
+    context: &MessageContext,
+    emoji: &ParsedEmoji,
+) -> Result<(String, Vec<u8>), GenericError> {
+    let (id, format) = match emoji {
+        ParsedEmoji::Custom { animated, id, .. } => (*id, if *animated { "gif" } else { "png" }),
+        ParsedEmoji::Unicode(_) => return Err("unicode emojis can't be exported".into()),
+    };
+
+    let buffer = steal::fetch(context, Uri::try_from(cdn::emoji(id, format))?).await?;
+
+    Ok((format!("{}.{}", id, format), buffer))
+}
+
+async fn export(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let (name, tier) = {
+        let guild = context
+            .cache()
+            .guild(guild_id)
+            .ok_or_else(|| KatzeError::guild_not_found(guild_id))?;
+
+        (guild.name().to_string(), guild.premium_tier())
+    };
+
+    // managed emojis belong to integrations, and can't be uploaded anywhere else
+    let mut emojis = guild_emojis(context, guild_id)
+        .into_values()
+        .filter(|emoji| !emoji.managed)
+        .map(|emoji| emoji.parsed)
+        .collect::<Vec<ParsedEmoji>>();
+
+    emojis.sort_by_key(|emoji| emoji.key());
+
+    if emojis.is_empty() {
+        let reply = context
+            .reply("This server has no emojis to export.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context
+        .http()
+        .create_typing_trigger(context.message.channel_id)
+        .exec()
+        .await?;
+
+    // built in a loop rather than a closure, so the future stays Send
+    let mut fetches = Vec::with_capacity(emojis.len());
+    for emoji in emojis.iter() {
+        fetches.push(pack_image(context, emoji));
+    }
+
+    let images = stream::iter(fetches)
+        .buffered(PACK_CONCURRENCY)
+        .collect::<Vec<Result<(String, Vec<u8>), GenericError>>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<(String, Vec<u8>)>, GenericError>>()?;
+
+    // emoji names aren't unique, so files are named by id, and the manifest has the names
+    let manifest = emojis
+        .iter()
+        .zip(images.iter())
+        .filter_map(|(emoji, (file, _))| match emoji {
+            ParsedEmoji::Custom { animated, name, .. } => Some(json!({
+                "name": name,
+                "animated": animated,
+                "file": file,
+            })),
+            // guild emojis are always custom
+            ParsedEmoji::Unicode(_) => None,
+        })
+        .collect::<Vec<Value>>();
+
+    let manifest = json!({
+        "guild": name,
+        "emojis": manifest,
+    });
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    writer.start_file(PACK_MANIFEST, FileOptions::default())?;
+    writer.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+
+    // images are already compressed
+    for (file, buffer) in images.iter() {
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file(file.as_str(), options)?;
+        writer.write_all(buffer)?;
+    }
+
+    let zipped = writer.finish()?.into_inner();
+
+    // boosted servers can upload larger files
+    let limit = match tier {
+        PremiumTier::Tier2 => 50,
+        PremiumTier::Tier3 => 100,
+        _ => 8,
+    } * 1024
+        * 1024;
+
+    if zipped.len() > limit {
+        let reply = context
+            .reply(format!(
+                "The pack is {} MiB, which is too large to upload here.",
+                zipped.len() / 1024 / 1024
+            ))
+            .await?;
+
+        return Ok(Response::Message(reply));
+    }
+
+    let attachment = AttachmentFile::from_bytes("emojis.zip", &zipped);
+    let content = format!("**{}** emojis from **{}**", emojis.len(), name);
+
+    let reply = context
+        .http()
+        .create_message(context.message.channel_id)
+        .content(&content)?
+        .attach(&[attachment])
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+// read a file from a pack, refusing to read more than the limit. files in a zip can unpack into
+// far more than they take up, so the sizes they claim can't be trusted.
+fn read_limited(file: impl Read, limit: u64) -> Result<Vec<u8>, GenericError> {
+    let mut buffer = Vec::new();
+    file.take(limit + 1).read_to_end(&mut buffer)?;
+
+    if buffer.len() as u64 > limit {
+        return Err(format!("a file is bigger than {} KiB", limit / 1024).into());
+    }
+
+    Ok(buffer)
+}
+
+// read the name, animated flag and image of every emoji in a pack
+fn read_pack(zipped: Vec<u8>) -> Result<Vec<(String, bool, Vec<u8>)>, GenericError> {
+    let mut archive = ZipArchive::new(Cursor::new(zipped))?;
+
+    let manifest: Value = {
+        let file = archive.by_name(PACK_MANIFEST)?;
+        serde_json::from_slice(&read_limited(file, PACK_MANIFEST_LIMIT)?)?
+    };
+
+    let entries = manifest["emojis"]
+        .as_array()
+        .ok_or("the manifest has no emojis")?;
+
+    let mut emojis = Vec::new();
+    let mut unpacked = 0;
+
+    for entry in entries {
+        let (name, animated, file) = match (
+            entry["name"].as_str(),
+            entry["animated"].as_bool(),
+            entry["file"].as_str(),
+        ) {
+            (Some(name), Some(animated), Some(file)) => (name, animated, file),
+            _ => return Err("the manifest has an invalid emoji".into()),
+        };
+
+        let buffer = read_limited(archive.by_name(file)?, steal::EMOJI_LIMIT as u64)?;
+        unpacked += buffer.len() as u64;
+
+        if unpacked > PACK_UNPACKED_LIMIT {
+            return Err("the pack has more emojis than a server can hold".into());
+        }
+
+        emojis.push((name.to_string(), animated, buffer));
+    }
+
+    Ok(emojis)
+}
+
+async fn import(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_EMOJIS_AND_STICKERS).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_EMOJIS_AND_STICKERS).await?;
+
+    // the pack can be attached to the command, or to the message being replied to
+    let attachment = context.message.attachments.first().or_else(|| {
+        context
+            .message
+            .referenced_message
+            .as_ref()
+            .and_then(|reference| reference.attachments.first())
+    });
+
+    let attachment = match attachment {
+        Some(attachment) if attachment.size <= PACK_LIMIT => attachment,
+        Some(_) => {
+            let reply = context
+                .reply(format!(
+                    "Packs can be at most {} MiB.",
+                    PACK_LIMIT / 1024 / 1024
+                ))
+                .await?;
+            return Ok(Response::Message(reply));
+        }
+        None => {
+            let reply = context.reply(IMPORT_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let zipped = steal::fetch(context, Uri::try_from(attachment.url.as_str())?).await?;
+
+    let pack = match read_pack(zipped) {
+        Ok(pack) => pack,
+        Err(why) => {
+            let reply = context
+                .reply(format!("That isn't an emoji pack: {}", why))
+                .await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let mut existing = guild_emojis(context, guild_id)
+        .into_values()
+        .map(|emoji| emoji.name)
+        .collect::<HashSet<String>>();
+
+    let mut slots = Slots::free(context, guild_id);
+    let total = pack.len();
+    let mut imported = 0;
+    let mut skipped = 0;
+    let mut failed = Vec::new();
+
+    let status = context
+        .reply(format!("Importing {} emojis...", total))
+        .await?;
+
+    for (index, (name, animated, buffer)) in pack.into_iter().enumerate() {
+        let free = if animated {
+            &mut slots.animated
+        } else {
+            &mut slots.stills
+        };
+
+        if existing.contains(&name) {
+            skipped += 1;
+        } else if *free == 0 {
+            failed.push(format!("`{}`: there are no free slots", name));
+        } else {
+            match steal::create_emoji(context, &name, buffer).await {
+                Ok(_) => {
+                    *free -= 1;
+                    imported += 1;
+                    // a pack can have the same name more than once
+                    existing.insert(name);
+                }
+                Err(why) => failed.push(format!("`{}`: {}", name, why)),
+            }
+        }
+
+        if (index + 1) % PACK_PROGRESS == 0 && index + 1 < total {
+            let content = format!("Importing {} emojis... {}/{}", total, index + 1, total);
+
+            context
+                .http()
+                .update_message(status.channel_id, status.id)
+                .content(Some(&content))?
+                .exec()
+                .await?;
+        }
+    }
+
+    let content = format!(
+        "Imported **{}** of {} emojis, skipped **{}** that already exist.",
+        imported, total, skipped
+    );
+
+    context
+        .http()
+        .update_message(status.channel_id, status.id)
+        .content(Some(&content))?
+        .exec()
+        .await?;
+
+    // the failures could be too long for one message
+    if !failed.is_empty() {
+        let reply = context
+            .paginate(model::pages("**Couldn't import:**", failed))
+            .await?;
+
+        return Ok(Response::Message(reply));
+    }
+
+    Ok(Response::Message(status))
+}
+
+async fn purge(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let member = context
+        .option::<UserArg>("--user")
+        .await?
+        .map(|UserArg(user)| user);
+
+    if !context.args.is_empty() {
+        let reply = context.reply(PURGE_USAGE).await?;
+        return Ok(Response::Message(reply));
+    }
+
+    // anyone can purge their own history
+    if member.as_ref().map(|user| user.id) != Some(context.message.author.id) {
+        checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+    }
+
+    let prompt = match &member {
+        Some(user) => {
+            let retention = Setting::query(context.postgres().clone(), guild_id)
+                .await?
+                .emojis_retention;
+
+            // rolled up usage doesn't say who it was, so it can't be purged by user
+            format!(
+                "This will delete all recorded emoji usage by {}#{:04} in this server. Usage \
+                older than {} days is kept as daily totals for everyone, and will still be \
+                counted.\nAre you sure?",
+                user.name, user.discriminator, retention
+            )
+        }
+        None => {
+            String::from("This will delete all recorded emoji usage in this server.\nAre you sure?")
+        }
+    };
+
+    if !context.confirm(prompt).await? {
+        return Ok(Response::None);
+    }
+
+    // buffered usage would otherwise be written after the purge
+    context.emojis().flush().await?;
+
+    match member {
+        Some(user) => {
+            sqlx::query!(
+                "DELETE FROM emojis WHERE (guild_id = $1 AND member_id = $2);",
+                guild_id.to_string(),
+                user.id.to_string(),
+            )
+            .execute(context.postgres())
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM emojis WHERE (guild_id = $1);",
+                guild_id.to_string(),
+            )
+            .execute(context.postgres())
+            .await?;
+
+            sqlx::query!(
+                "DELETE FROM emoji_days WHERE (guild_id = $1);",
+                guild_id.to_string(),
+            )
+            .execute(context.postgres())
+            .await?;
+        }
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn retention(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let days = match context.optional_arg::<i32>().await? {
+        Some(days) if days >= MIN_RETENTION && days as i64 <= MAX_DAYS => days,
+        Some(_) => {
+            let reply = context.reply(RETENTION_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+        None => {
+            let setting = Setting::query(context.postgres().clone(), guild_id).await?;
+            let content = format!(
+                "Emoji usage is kept in full for {} days, and as daily totals after that.",
+                setting.emojis_retention
+            );
+
+            let reply = context.reply(content).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    sqlx::query!(
+        "UPDATE settings SET emojis_retention = $2 WHERE (guild_id = $1);",
+        guild_id.to_string(),
+        days,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn stats(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let filter = filter(context, USAGE).await?;
+
+    let emojis = guild_emojis(context, guild_id);
+
+    // a specific emoji shows more detail
+    if !filter.query.is_empty() {
+        let query = filter.query.join(" ");
+
+        let mut found = match emoji::parse_one(&query) {
+            Some(emoji @ ParsedEmoji::Unicode(_)) => return detail(context, &filter, &emoji).await,
+            Some(ParsedEmoji::Custom { id, .. }) => {
+                emojis.get(&id).map(|_| id).into_iter().collect()
+            }
+            None => search::best(
+                query.trim_matches(':'),
+                emojis
+                    .iter()
+                    .map(|(id, emoji)| (*id, vec![emoji.name.clone()])),
+            ),
+        };
+
+        let emoji_id = if found.len() > 1 {
+            let options = found
+                .iter()
+                .map(|id| format!("{} `{}`", emojis[id].formatted, emojis[id].name))
+                .collect::<Vec<String>>();
+
+            context
+                .disambiguate(&options)
+                .await?
+                .map(|index| found.swap_remove(index))
+        } else {
+            found.pop()
+        };
+
+        return match emoji_id {
+            Some(emoji_id) => detail(context, &filter, &emojis[&emoji_id].parsed).await,
+            None => {
+                let reply = context
+                    .reply("Couldn't find that emoji in this server.")
+                    .await?;
+
+                Ok(Response::Message(reply))
+            }
+        };
+    }
+
+    // only count emojis that still exist, so deleted emojis don't show up
+    let mut counts = counts(
+        context,
+        guild_id,
+        &emojis,
+        filter.since,
+        filter.member_id(),
+        filter.reaction,
+    )
+    .await?;
+
+    // the least used emojis include the ones that were never used at all
+    if filter.least {
+        let mut unused = emojis
+            .keys()
+            .filter(|id| !counts.iter().any(|(used, _)| used == *id))
+            .map(|id| (*id, 0))
+            .collect::<Vec<(EmojiId, i64)>>();
+
+        unused.sort_by_key(|(id, _)| Reverse(*id));
+        counts.extend(unused);
+        counts.reverse();
+    }
+
+    let mut counts = counts
+        .into_iter()
+        .map(|(id, count)| (emojis[&id].formatted.clone(), count))
+        .collect::<Vec<(String, i64)>>();
+
+    // unicode emojis are ranked alongside custom ones, but can't be listed as never used
+    if !filter.least {
+        let unicode = unicode_counts(
+            context,
+            guild_id,
+            filter.since,
+            filter.member_id(),
+            filter.reaction,
+        )
+        .await?;
+
+        counts.extend(unicode);
+        counts.sort_by_key(|(_, count)| Reverse(*count));
+    }
+
+    if counts.is_empty() {
+        let reply = context
+            .reply(format!("No emojis were used {}.", filter.describe()))
+            .await?;
+
+        return Ok(Response::Message(reply));
+    }
+
+    let lines = counts
+        .chunks(PER_LINE)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|(formatted, count)| format!("`{}` {}", count, formatted))
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect::<Vec<String>>();
+
+    let header = if filter.least {
+        format!("**Least used emojis {}**", filter.describe())
+    } else {
+        format!("**Most used emojis {}**", filter.describe())
+    };
+
+    let reply = context.paginate(model::pages(&header, lines)).await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn tracking(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let enabled = match context.subcommand().as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => {
+            let reply = context.reply(TRACKING_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+        None => {
+            let setting = Setting::query(context.postgres().clone(), guild_id).await?;
+            let content = if setting.emojis_enabled {
+                "Emoji usage is being recorded in this server."
+            } else {
+                "Emoji usage is not being recorded in this server."
+            };
+
+            let reply = context.reply(content).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    sqlx::query!(
+        "UPDATE settings SET emojis_enabled = $2 WHERE (guild_id = $1);",
+        guild_id.to_string(),
+        enabled,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.emojis().forget_settings(guild_id);
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+// join a list of names, stopping after a few so it still fits in a message
+fn abbreviate(names: &[String]) -> String {
+    if names.len() <= MAX_NAMES {
+        return names.join(" ");
+    }
+
+    format!(
+        "{} and {} more",
+        names[..MAX_NAMES].join(" "),
+        names.len() - MAX_NAMES
+    )
+}
+
+async fn unused(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let delete = context.flag("--delete");
+    let max = context.option::<i64>("--max").await?.unwrap_or(0);
+    let days = context.optional_arg::<i64>().await?.unwrap_or(UNUSED_DAYS);
+
+    if max < 0 || days <= 0 || days > MAX_DAYS || !context.args.is_empty() {
+        let reply = context.reply(UNUSED_USAGE).await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let now = Utc::now();
+    let since = (now - Duration::days(days)).timestamp();
+
+    // emojis added during the window haven't had a fair chance to be used, and managed emojis
+    // belong to integrations, so neither are reported
+    let emojis = guild_emojis(context, guild_id)
+        .into_iter()
+        .filter(|(id, emoji)| !emoji.managed && model::created_at(id.get()) as i64 <= since)
+        .collect::<HashMap<EmojiId, GuildEmoji>>();
+
+    let counts = counts(context, guild_id, &emojis, since, None, None).await?;
+
+    let mut found = emojis
+        .keys()
+        .map(|id| {
+            let count = counts
+                .iter()
+                .find(|(used, _)| used == id)
+                .map(|(_, count)| *count)
+                .unwrap_or_default();
+
+            (*id, count)
+        })
+        .filter(|(_, count)| *count <= max)
+        .collect::<Vec<(EmojiId, i64)>>();
+
+    found.sort_by_key(|(id, count)| (*count, *id));
+
+    let description = if max == 0 {
+        format!("not used in the last {} days", days)
+    } else {
+        format!("used at most {} time(s) in the last {} days", max, days)
+    };
+
+    if found.is_empty() {
+        let reply = context
+            .reply(format!("There are no emojis {}.", description))
+            .await?;
+
+        return Ok(Response::Message(reply));
+    }
+
+    if !delete {
+        let lines = found
+            .chunks(PER_LINE)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|(id, count)| format!("`{}` {}", count, emojis[id].formatted))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect::<Vec<String>>();
+
+        let header = format!("**{} emoji(s) {}**", found.len(), description);
+        let reply = context.paginate(model::pages(&header, lines)).await?;
+
+        return Ok(Response::Message(reply));
+    }
+
+    checks::has_permission(context, Permissions::MANAGE_EMOJIS_AND_STICKERS).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_EMOJIS_AND_STICKERS).await?;
+
+    let names = found
+        .iter()
+        .map(|(id, _)| format!("`{}`", emojis[id].name))
+        .collect::<Vec<String>>();
+
+    let confirmed = context
+        .confirm(format!(
+            "This will delete {} emoji(s) {}: {}\nAre you sure?",
+            found.len(),
+            description,
+            abbreviate(&names)
+        ))
+        .await?;
+
+    if !confirmed {
+        return Ok(Response::None);
+    }
+
+    context
+        .http()
+        .create_typing_trigger(context.message.channel_id)
+        .exec()
+        .await?;
+
+    let mut failed = Vec::new();
+    for (id, _) in found.iter() {
+        let result = context
+            .http()
+            .delete_emoji(guild_id, *id)
+            .reason("Automated unused emoji cleanup")?
+            .exec()
+            .await;
+
+        if result.is_err() {
+            failed.push(format!("`{}`", emojis[id].name));
+        }
+    }
+
+    let content = if failed.is_empty() {
+        format!("Deleted {} emoji(s).", found.len())
+    } else {
+        format!(
+            "Deleted {} emoji(s). Couldn't delete {}.",
+            found.len() - failed.len(),
+            abbreviate(&failed)
+        )
+    };
+
+    let reply = context.reply(content).await?;
+
+    Ok(Response::Message(reply))
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    // make sure the most recent usage is counted
+    context.emojis().flush().await?;
+
+    // anything else is an option for the stats
+    match context.subcommand_of(&[
+        "chart",
+        "dedupe",
+        "export",
+        "import",
+        "purge",
+        "retention",
+        "tracking",
+        "unused",
+    ]) {
+        Some("chart") => chart(context).await,
+        Some("dedupe") => dedupe(context).await,
+        Some("export") => export(context).await,
+        Some("import") => import(context).await,
+        Some("purge") => purge(context).await,
+        Some("retention") => retention(context).await,
+        Some("tracking") => tracking(context).await,
+        Some("unused") => unused(context).await,
+        _ => stats(context).await,
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{
+    args::{RoleArg, UserArg},
+    cdn,
+    error::KatzeError,
+    model::{self, GenericError, MessageContext, Response},
+    table::{primitive::I64, Setting},
+};
+use std::str;
+use twilight_embed_builder::{EmbedBuilder, EmbedFieldBuilder, ImageSource};
+use twilight_mention::Mention;
+use twilight_model::guild::Role;
+
+fn format_time(seconds: u64) -> String {
+    format!("<t:{0}:D> (<t:{0}:R>)", seconds)
+}
+
+// embed field values can only be 1024 characters long, so cut off the list at a whole item
+fn format_list(items: Vec<String>) -> String {
+    let mut content = String::new();
+
+    for (index, item) in items.iter().enumerate() {
+        let remaining = items.len() - index;
+        let suffix = format!(" and {} more", remaining);
+
+        if content.len() + item.len() + suffix.len() + 1 > 1024 {
+            content.push_str(&suffix);
+            break;
+        }
+
+        if !content.is_empty() {
+            content.push(' ');
+        }
+
+        content.push_str(item);
+    }
+
+    if content.is_empty() {
+        content.push_str("None");
+    }
+
+    content
+}
+
+pub async fn roleinfo(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let role = match context.optional_rest::<RoleArg>().await? {
+        Some(RoleArg(role)) => role,
+        None => {
+            let reply = context.reply("USAGE: katze roleinfo <role>").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    // count the cached members that have this role
+    let members = context
+        .cache()
+        .guild_members(guild_id)
+        .map(|members| {
+            members
+                .iter()
+                .filter_map(|id| context.cache().member(guild_id, *id))
+                .filter(|member| member.roles().contains(&role.id))
+                .count()
+        })
+        .unwrap_or_default();
+
+    let permissions = format!("{:?}", role.permissions);
+
+    let embed = EmbedBuilder::new()
+        .title(role.name.clone())
+        .color(role.color)
+        .field(EmbedFieldBuilder::new("ID", role.id.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Color", format!("#{:06x}", role.color)).inline())
+        .field(EmbedFieldBuilder::new("Position", role.position.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Members", members.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Hoisted", role.hoist.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Mentionable", role.mentionable.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Managed", role.managed.to_string()).inline())
+        .field(EmbedFieldBuilder::new(
+            "Created",
+            format_time(model::created_at(role.id.get())),
+        ))
+        .field(EmbedFieldBuilder::new(
+            "Permissions",
+            format!("`{}`", permissions),
+        ))
+        .build()?;
+
+    let reply = context.reply_embed(embed).await?;
+
+    Ok(Response::Message(reply))
+}
+
+pub async fn serverinfo(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let (name, owner_id, icon, member_count, boosts, tier) = {
+        let guild = context
+            .cache()
+            .guild(guild_id)
+            .ok_or_else(|| KatzeError::guild_not_found(guild_id))?;
+
+        (
+            guild.name().to_string(),
+            guild.owner_id(),
+            guild.icon().map(String::from),
+            guild.member_count().unwrap_or_default(),
+            guild.premium_subscription_count().unwrap_or_default(),
+            guild.premium_tier() as u8,
+        )
+    };
+
+    let (emojis, animated_emojis) = context
+        .cache()
+        .guild_emojis(guild_id)
+        .map(|emojis| {
+            emojis
+                .iter()
+                .filter_map(|id| context.cache().emoji(*id))
+                .fold((0, 0), |(emojis, animated), emoji| {
+                    if emoji.animated() {
+                        (emojis, animated + 1)
+                    } else {
+                        (emojis + 1, animated)
+                    }
+                })
+        })
+        .unwrap_or_default();
+
+    let roles = context
+        .cache()
+        .guild_roles(guild_id)
+        .map(|roles| roles.len())
+        .unwrap_or_default();
+
+    let channels = context
+        .cache()
+        .guild_channels(guild_id)
+        .map(|channels| channels.len())
+        .unwrap_or_default();
+
+    // icon rotation stats
+    let setting = Setting::query(context.postgres().clone(), guild_id).await?;
+
+    let count = sqlx::query_as!(
+        I64,
+        "SELECT
+            COUNT(message_id) AS \"result!: _\"
+        FROM images WHERE
+        (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_one(context.postgres())
+    .await?;
+
+    let mut redis = context.redis().get().await;
+    let last_time = redis.hget("rr-rs:rotations", guild_id.to_string()).await?;
+
+    let rotation = if setting.rotate_enabled {
+        let mut rotation = format!(
+            "**{}** image(s), every {} hour(s)",
+            *count, setting.rotate_every
+        );
+
+        if let Some(last_time) = last_time {
+            let last_time = str::from_utf8(&last_time)?.parse::<u64>()?;
+            rotation.push_str(&format!("\nLast rotated <t:{}:R>", last_time));
+        }
+
+        rotation
+    } else {
+        format!("**{}** image(s), rotation disabled", *count)
+    };
+
+    let mut embed = EmbedBuilder::new()
+        .title(name)
+        .field(EmbedFieldBuilder::new("Owner", owner_id.mention().to_string()).inline())
+        .field(EmbedFieldBuilder::new("Members", member_count.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Boosts", format!("{} (tier {})", boosts, tier)).inline())
+        .field(
+            EmbedFieldBuilder::new(
+                "Emojis",
+                format!("{} static, {} animated", emojis, animated_emojis),
+            )
+            .inline(),
+        )
+        .field(EmbedFieldBuilder::new("Roles", roles.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Channels", channels.to_string()).inline())
+        .field(EmbedFieldBuilder::new(
+            "Created",
+            format_time(model::created_at(guild_id.get())),
+        ))
+        .field(EmbedFieldBuilder::new("Icon rotation", rotation));
+
+    if let Some(icon) = icon {
+        let url = cdn::icon(guild_id, &icon, cdn::formats(&icon)[0], 256);
+        embed = embed.thumbnail(ImageSource::url(url)?);
+    }
+
+    let reply = context.reply_embed(embed.build()?).await?;
+
+    Ok(Response::Message(reply))
+}
+
+pub async fn whois(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let user = match context.optional_rest::<UserArg>().await? {
+        Some(UserArg(user)) => user,
+        None => context.message.author.clone(),
+    };
+
+    let avatar = match &user.avatar {
+        Some(hash) => cdn::avatar(user.id, hash, cdn::formats(hash)[0], 256),
+        None => cdn::default_avatar(user.discriminator),
+    };
+
+    let mut embed = EmbedBuilder::new()
+        .title(format!("{}#{:04}", user.name, user.discriminator))
+        .thumbnail(ImageSource::url(avatar)?)
+        .field(EmbedFieldBuilder::new("ID", user.id.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Bot", user.bot.to_string()).inline())
+        .field(EmbedFieldBuilder::new(
+            "Account created",
+            format_time(model::created_at(user.id.get())),
+        ));
+
+    let member = context
+        .cache()
+        .member(guild_id, user.id)
+        .map(|member| member.value().clone());
+
+    if let Some(member) = member {
+        // highest roles first
+        let mut roles = member
+            .roles()
+            .iter()
+            .filter_map(|id| {
+                context
+                    .cache()
+                    .role(*id)
+                    .map(|role| role.resource().clone())
+            })
+            .collect::<Vec<Role>>();
+
+        roles.sort_by(|a, b| b.position.cmp(&a.position).then(a.id.cmp(&b.id)));
+
+        if let Some(nick) = member.nick() {
+            embed = embed.field(EmbedFieldBuilder::new("Nickname", nick).inline());
+        }
+
+        if let Some(joined_at) = member.joined_at() {
+            embed = embed.field(EmbedFieldBuilder::new(
+                "Joined server",
+                format_time(joined_at.as_secs()),
+            ));
+        }
+
+        if let Some(premium_since) = member.premium_since() {
+            embed = embed.field(EmbedFieldBuilder::new(
+                "Boosting since",
+                format_time(premium_since.as_secs()),
+            ));
+        }
+
+        if let Some(color) = roles
+            .iter()
+            .map(|role| role.color)
+            .find(|color| *color != 0)
+        {
+            embed = embed.color(color);
+        }
+
+        let mentions = roles
+            .iter()
+            .map(|role| role.id.mention().to_string())
+            .collect::<Vec<String>>();
+
+        embed = embed.field(EmbedFieldBuilder::new(
+            format!("Roles ({})", roles.len()),
+            format_list(mentions),
+        ));
+    }
+
+    let reply = context.reply_embed(embed.build()?).await?;
+
+    Ok(Response::Message(reply))
+}
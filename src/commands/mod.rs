@@ -1,5 +1,89 @@
 pub mod admin;
+pub mod emojis;
 pub mod fun;
+pub mod info;
+pub mod owner;
+pub mod perms;
+pub mod rolecolor;
+pub mod rolegroup;
 pub mod roleme;
+pub mod rolemenu;
+pub mod rolepersist;
 pub mod rotate;
+pub mod steal;
+pub mod temprole;
+pub mod track;
 pub mod util;
+
+use crate::model::{GenericError, MessageContext, Response};
+
+/// A command, with the other names it can be called by, and the category it's in.
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub category: &'static str,
+}
+
+impl Command {
+    /// The command a word from a message calls, by its name or one of its aliases.
+    pub fn find(word: &str) -> Option<&'static Command> {
+        COMMANDS
+            .iter()
+            .find(|command| command.name == word || command.aliases.contains(&word))
+    }
+}
+
+// every command is listed once, and both the table of commands and the function that runs them
+// come from the list, so they can't disagree
+macro_rules! commands {
+    ($context:ident; $($name:literal $(| $alias:literal)* in $category:literal => $run:expr,)*) => {
+        /// Every command, by name.
+        pub const COMMANDS: &[Command] = &[
+            $(Command {
+                name: $name,
+                aliases: &[$($alias),*],
+                category: $category,
+            }),*
+        ];
+
+        /// Run a command by its name.
+        pub async fn run(
+            name: &str,
+            $context: &mut MessageContext,
+        ) -> Result<Response, GenericError> {
+            match name {
+                $($name => $run.await,)*
+                _ => Ok(Response::None),
+            }
+        }
+    };
+}
+
+commands! {
+    context;
+    "add_image" | "pls" in "rotate" => rotate::add_image(context),
+    "avatar" in "info" => util::avatar(context),
+    "change-avatar" in "admin" => admin::change_avatar(context),
+    "choose" in "fun" => util::choose(context),
+    "count" in "rotate" => rotate::count(context),
+    "delete" | "remove" | "rm" in "rotate" => rotate::delete(context),
+    "emojis" in "emojis" => emojis::execute(context),
+    "help" in "info" => util::help(context),
+    "invite" in "info" => util::invite(context),
+    "list" | "ls" in "rotate" => rotate::list(context),
+    "owner" in "admin" => owner::execute(context),
+    "owo" in "fun" => fun::owo(context),
+    "perms" in "admin" => perms::execute(context),
+    "pick" in "rotate" => rotate::pick(context),
+    "ping" | "pong" in "info" => util::ping(context),
+    "roleinfo" in "info" => info::roleinfo(context),
+    "roleme" in "roles" => roleme::execute(context),
+    "rotate" | "rotato" | "tomato" | "potato" | "🍅" | "🥔" in "rotate" => rotate::execute(context),
+    "serverinfo" | "guildinfo" in "info" => info::serverinfo(context),
+    "show" in "rotate" => rotate::show(context),
+    "shuffle" in "fun" => util::shuffle(context),
+    "steal" in "emojis" => steal::execute(context),
+    "temprole" in "roles" => temprole::execute(context),
+    "track" | "vtrack" in "track" => track::execute(context),
+    "whois" | "userinfo" in "info" => info::whois(context),
+}
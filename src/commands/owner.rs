@@ -0,0 +1,152 @@
+use crate::{
+    checks,
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
+    staff::Staff,
+};
+use chrono::Duration;
+use chrono_humanize::{Accuracy, HumanTime, Tense};
+use std::{cmp::Reverse, time::Instant};
+use twilight_model::id::GuildId;
+
+const USAGE: &str = "USAGE: katze owner [status | guilds | leave | reload]";
+const LEAVE_USAGE: &str = "USAGE: katze owner leave <guild id>";
+
+async fn status(context: &MessageContext) -> Result<Response, GenericError> {
+    checks::is_staff(context)?;
+
+    let uptime = Duration::from_std(context.uptime())?;
+
+    // a round trip to each database, to see if either is struggling
+    let started = Instant::now();
+    sqlx::query!("SELECT 1 AS \"one!\";")
+        .fetch_one(context.postgres())
+        .await?;
+    let postgres = started.elapsed();
+
+    let started = Instant::now();
+    context.redis().get().await.ping().await?;
+    let redis = started.elapsed();
+
+    let stats = context.cache().stats();
+    let staff = context.staff();
+
+    let content = format!(
+        "**katze {}**\n\
+        Up for {}\n\
+        Guilds: {} ({} unavailable)\n\
+        Cached: {} users, {} members, {} roles, {} emojis\n\
+        Postgres: {} connections ({} idle), {}ms\n\
+        Redis: {}ms\n\
+        Owners: {}, staff: {}",
+        env!("CARGO_PKG_VERSION"),
+        HumanTime::from(uptime).to_text_en(Accuracy::Rough, Tense::Present),
+        stats.guilds(),
+        stats.unavailable_guilds(),
+        stats.users(),
+        stats.members(),
+        stats.roles(),
+        stats.emojis(),
+        context.postgres().size(),
+        context.postgres().num_idle(),
+        postgres.as_millis(),
+        redis.as_millis(),
+        staff.owners(),
+        staff.staff(),
+    );
+
+    let reply = context.reply(content).await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn guilds(context: &MessageContext) -> Result<Response, GenericError> {
+    checks::is_staff(context)?;
+
+    let mut guilds = context
+        .cache()
+        .iter()
+        .guilds()
+        .map(|guild| {
+            (
+                guild.member_count().unwrap_or_default(),
+                guild.id(),
+                guild.name().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // the biggest guilds first
+    guilds.sort_by_key(|(members, _, _)| Reverse(*members));
+
+    let header = format!("**{} guild(s):**", guilds.len());
+    let lines = guilds
+        .into_iter()
+        .map(|(members, id, name)| format!("`{}` **{}**, {} members", id, name, members));
+
+    let reply = context.paginate(model::pages(&header, lines)).await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn leave(context: &mut MessageContext) -> Result<Response, GenericError> {
+    checks::is_owner(context)?;
+
+    let guild_id = match context.optional_arg::<u64>().await?.and_then(GuildId::new) {
+        Some(guild_id) => guild_id,
+        None => {
+            let reply = context.reply(LEAVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let name = match context.cache().guild(guild_id) {
+        Some(guild) => guild.name().to_string(),
+        None => {
+            let reply = context.reply("I'm not in that guild.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    if !context.confirm(format!("Leave **{}**?", name)).await? {
+        return Ok(Response::None);
+    }
+
+    context.http().leave_guild(guild_id).exec().await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn reload(context: &MessageContext) -> Result<Response, GenericError> {
+    checks::is_owner(context)?;
+
+    let staff = Staff::reload(context.http()).await?;
+    let (owners, members) = (staff.owners(), staff.staff());
+
+    context.set_staff(staff);
+
+    let reply = context
+        .reply(format!(
+            "Reloaded the config. There are {} owner(s) and {} staff.",
+            owners, members
+        ))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("status") | None => status(context).await,
+        Some("guilds") => guilds(context).await,
+        Some("leave") => leave(context).await,
+        Some("reload") => reload(context).await,
+        Some(_) => {
+            checks::is_staff(context)?;
+
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
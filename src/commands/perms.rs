@@ -0,0 +1,275 @@
+use crate::{
+    args::{Arg, ChannelArg, RoleArg, UserArg},
+    checks,
+    commands::COMMANDS,
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
+    table::CommandRule,
+};
+use twilight_model::{
+    guild::Permissions,
+    id::{RoleId, UserId},
+};
+
+const ALL: &str = "all";
+const CATEGORY: &str = "category:";
+const MAX_RULES: i64 = 100;
+
+// perms can't be restricted, so a server can't lock itself out of changing its rules, and servers
+// don't get a say in who runs the bot
+const EXEMPT: [&str; 2] = ["owner", "perms"];
+
+const USAGE: &str = "USAGE: katze perms [list | allow | deny | remove]";
+const RULE_USAGE: &str = "USAGE: katze perms <allow | deny> <command | category:<category> | all> <role | member | channel>";
+const REMOVE_USAGE: &str = "USAGE: katze perms remove <id>";
+
+/// Whether a command ignores the server's rules.
+pub fn exempt(command: &str) -> bool {
+    EXEMPT.contains(&command)
+}
+
+/// The scope of the rules for a command's category. Categories are prefixed, because some of them
+/// share a name with a command.
+pub fn category(command: &str) -> Option<String> {
+    COMMANDS
+        .iter()
+        .find(|found| found.name == command)
+        .map(|found| format!("{}{}", CATEGORY, found.category))
+}
+
+fn categories() -> Vec<&'static str> {
+    let mut categories = COMMANDS
+        .iter()
+        .map(|command| command.category)
+        .collect::<Vec<_>>();
+
+    categories.sort_unstable();
+    categories.dedup();
+    categories
+}
+
+// what a rule can be set for: a command by its name, a category, or all of them
+fn is_scope(scope: &str) -> bool {
+    match scope.strip_prefix(CATEGORY) {
+        Some(category) => categories().contains(&category),
+        None => scope == ALL || COMMANDS.iter().any(|c| c.name == scope && !exempt(c.name)),
+    }
+}
+
+// what a rule applies to, as its kind and id. channels and roles are tried
+// before members, because an id could be any of them.
+async fn target(
+    context: &MessageContext,
+    query: &str,
+) -> Result<Option<(&'static str, String)>, GenericError> {
+    if let Ok(ChannelArg(channel)) = ChannelArg::parse(context, query).await {
+        return Ok(Some(("channel", channel.id().to_string())));
+    }
+
+    if let Ok(RoleArg(role)) = RoleArg::parse(context, query).await {
+        return Ok(Some(("role", role.id.to_string())));
+    }
+
+    if let Ok(UserArg(user)) = UserArg::parse(context, query).await {
+        return Ok(Some(("user", user.id.to_string())));
+    }
+
+    Ok(None)
+}
+
+fn describe(context: &MessageContext, rule: &CommandRule) -> String {
+    let id = rule.target_id.parse::<u64>().ok();
+
+    match rule.kind.as_str() {
+        "channel" => format!("in <#{}>", rule.target_id),
+        "role" => {
+            let name = id
+                .and_then(|id| {
+                    context
+                        .cache()
+                        .role(RoleId::new(id)?)
+                        .map(|role| role.name.clone())
+                })
+                .unwrap_or_else(|| String::from("a deleted role"));
+
+            format!("for `{}`", name)
+        }
+        _ => {
+            let name = id
+                .and_then(|id| {
+                    context
+                        .cache()
+                        .user(UserId::new(id)?)
+                        .map(|user| user.name.clone())
+                })
+                .unwrap_or_else(|| rule.target_id.clone());
+
+            format!("for **{}**", name)
+        }
+    }
+}
+
+async fn list(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let rules = sqlx::query_as!(
+        CommandRule,
+        "SELECT id, command, kind, target_id, allow FROM command_rules WHERE
+        (guild_id = $1)
+        ORDER BY command, id;",
+        guild_id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    if rules.is_empty() {
+        let reply = context
+            .reply("There aren't any command rules in this server, so every command is allowed everywhere.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let lines = rules.iter().map(|rule| {
+        format!(
+            "`{}` {} `{}` {}",
+            rule.id,
+            if rule.allow { "Allow" } else { "Deny" },
+            rule.command,
+            describe(context, rule)
+        )
+    });
+
+    let reply = context
+        .paginate(model::pages("Command rules:", lines))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn set(context: &mut MessageContext, allow: bool) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    let (scope, query) = match (
+        context.optional_arg::<String>().await?,
+        context.optional_rest::<String>().await?,
+    ) {
+        (Some(scope), Some(query)) => (scope.to_lowercase(), query),
+        _ => {
+            let reply = context.reply(RULE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    if !is_scope(&scope) {
+        let categories = categories()
+            .into_iter()
+            .map(|category| format!("`{}{}`", CATEGORY, category))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let reply = context
+            .reply(format!(
+                "`{}` isn't a command or a category. The categories are {}.",
+                scope, categories
+            ))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let (kind, target_id) = match target(context, &query).await? {
+        Some(target) => target,
+        None => {
+            let reply = context
+                .reply(format!(
+                    "Couldn't find a channel, role, or member `{}`.",
+                    query
+                ))
+                .await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let count = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM command_rules WHERE (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_one(context.postgres())
+    .await?
+    .count;
+
+    if count >= MAX_RULES {
+        let reply = context
+            .reply(format!(
+                "This server already has {} command rules. Remove one first.",
+                MAX_RULES
+            ))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    // setting a rule for the same thing again flips it
+    sqlx::query!(
+        "INSERT INTO command_rules (guild_id, command, kind, target_id, allow)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (guild_id, command, kind, target_id) DO UPDATE SET allow = EXCLUDED.allow;",
+        guild_id.to_string(),
+        scope,
+        kind,
+        target_id,
+        allow,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    let id = match context.optional_arg::<i32>().await? {
+        Some(id) => id,
+        None => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let removed = sqlx::query!(
+        "DELETE FROM command_rules WHERE (guild_id = $1 AND id = $2);",
+        guild_id.to_string(),
+        id,
+    )
+    .execute(context.postgres())
+    .await?
+    .rows_affected();
+
+    if removed == 0 {
+        let reply = context
+            .reply("There's no command rule with that id.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("allow") => set(context, true).await,
+        Some("deny") => set(context, false).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        Some("list") | Some("ls") | None => list(context).await,
+        Some(_) => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
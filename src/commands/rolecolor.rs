@@ -0,0 +1,477 @@
+use crate::{
+    args::{self, Arg, ColorArg},
+    chart, checks,
+    commands::roleme,
+    model::{GenericError, MessageContext, Response, ResponseReaction},
+    table::PaletteColor,
+};
+use image::Rgba;
+use sqlx::{Error as SqlxError, PgPool};
+use std::collections::HashSet;
+use twilight_http::request::{AttachmentFile, AuditLogReason};
+use twilight_model::{
+    guild::Permissions,
+    id::{GuildId, RoleId},
+};
+
+const MAX_PALETTE: i64 = 25;
+const MAX_NAME: usize = 32;
+const MEMBER_PAGE: u64 = 1000;
+
+const USAGE: &str = "USAGE: katze roleme color [<color> | none | palette | cleanup]";
+const ADD_USAGE: &str = "USAGE: katze roleme color palette add <color> [<name>]";
+const REMOVE_USAGE: &str = "USAGE: katze roleme color palette remove <color>";
+
+fn hex(color: u32) -> String {
+    format!("#{:06x}", color)
+}
+
+fn rgba(color: u32) -> Rgba<u8> {
+    Rgba([(color >> 16) as u8, (color >> 8) as u8, color as u8, 255])
+}
+
+pub async fn colors(postgres: &PgPool, guild_id: GuildId) -> Result<Vec<PaletteColor>, SqlxError> {
+    sqlx::query_as!(
+        PaletteColor,
+        "SELECT color, name FROM roleme_palette WHERE
+        (guild_id = $1)
+        ORDER BY color;",
+        guild_id.to_string(),
+    )
+    .fetch_all(postgres)
+    .await
+}
+
+// every role that at least one member of the guild has. the cache doesn't have every member, so
+// this asks discord, a page at a time.
+async fn held(
+    context: &MessageContext,
+    guild_id: GuildId,
+) -> Result<HashSet<RoleId>, GenericError> {
+    let mut held = HashSet::new();
+    let mut after = None;
+
+    loop {
+        let mut request = context.http().guild_members(guild_id).limit(MEMBER_PAGE)?;
+
+        if let Some(after) = after {
+            request = request.after(after);
+        }
+
+        let members = request.exec().await?.model().await?;
+        held.extend(
+            members
+                .iter()
+                .flat_map(|member| member.roles.iter().copied()),
+        );
+
+        match members.last() {
+            Some(last) if members.len() as u64 == MEMBER_PAGE => after = Some(last.user.id),
+            _ => break,
+        }
+    }
+
+    Ok(held)
+}
+
+// every role that a member of the guild other than the author has, if the cache has every member.
+// the author is left out because the cache may not have seen their roles change yet.
+fn held_cached(context: &MessageContext, guild_id: GuildId) -> Option<HashSet<RoleId>> {
+    let member_count = context.cache().guild(guild_id)?.member_count()?;
+    let members = context.cache().guild_members(guild_id)?.clone();
+
+    if (members.len() as u64) < member_count {
+        return None;
+    }
+
+    let held = members
+        .into_iter()
+        .filter(|id| *id != context.message.author.id)
+        .filter_map(|id| context.cache().member(guild_id, id))
+        .flat_map(|member| member.roles().to_vec())
+        .collect();
+
+    Some(held)
+}
+
+// delete the author's old color roles if nobody else has them. asking discord for every member
+// on every pick would be too slow for big servers, so if the cache doesn't have every member,
+// they're left for cleanup instead.
+async fn drop_unused(
+    context: &MessageContext,
+    guild_id: GuildId,
+    candidates: Vec<RoleId>,
+) -> Result<(), GenericError> {
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(held) = held_cached(context, guild_id) {
+        clean(context, guild_id, candidates, &held).await?;
+    }
+
+    Ok(())
+}
+
+// delete the color roles that nobody has anymore, returning how many were deleted
+async fn clean(
+    context: &MessageContext,
+    guild_id: GuildId,
+    candidates: Vec<RoleId>,
+    held: &HashSet<RoleId>,
+) -> Result<usize, GenericError> {
+    let mut deleted = 0;
+
+    for role_id in candidates.into_iter().filter(|id| !held.contains(id)) {
+        context
+            .http()
+            .delete_role(guild_id, role_id)
+            .reason("Automated color role cleanup")?
+            .exec()
+            .await?;
+
+        sqlx::query!(
+            "DELETE FROM roleme_roles WHERE (id = $1);",
+            role_id.to_string(),
+        )
+        .execute(context.postgres())
+        .await?;
+
+        deleted += 1;
+    }
+
+    Ok(deleted)
+}
+
+// the color roles the author of the message has, other than the one they're picking
+async fn worn(
+    context: &MessageContext,
+    except: Option<RoleId>,
+) -> Result<Vec<RoleId>, GenericError> {
+    let current = context.author_roles();
+
+    let worn = roleme::roles(context)
+        .await?
+        .into_iter()
+        .filter(|r| r.color.is_some() && Some(r.id.0) != except)
+        .map(|r| r.id.0)
+        .filter(|id| current.contains(id))
+        .collect();
+
+    Ok(worn)
+}
+
+async fn pick(context: &mut MessageContext, query: &str) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+    let palette = colors(context.postgres(), guild_id).await?;
+
+    // with a palette, colors can be picked by name too, but only colors in the palette
+    let (color, name) = if palette.is_empty() {
+        let ColorArg(color) = ColorArg::parse(context, query).await?;
+        (color, None)
+    } else {
+        let wanted = ColorArg::parse(context, query)
+            .await
+            .ok()
+            .map(|ColorArg(color)| hex(color));
+
+        let found = palette.into_iter().find(|entry| {
+            Some(&entry.color) == wanted.as_ref()
+                || entry
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(query))
+        });
+
+        match found.and_then(|entry| Some((args::color(&entry.color)?, entry.name))) {
+            Some(found) => found,
+            None => {
+                let reply = context
+                    .reply("That color isn't in this server's palette. See the colors with `katze roleme color palette`.")
+                    .await?;
+                return Ok(Response::Message(reply));
+            }
+        }
+    };
+
+    if color == 0 {
+        let reply = context
+            .reply("Discord shows `#000000` as no color at all. Try `#010101` instead.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let existing = roleme::roles(context)
+        .await?
+        .into_iter()
+        .find(|r| r.color.as_deref() == Some(hex(color).as_str()))
+        .map(|r| r.id.0);
+
+    // color roles are shared by everyone who picks the same color
+    let role_id = match existing {
+        Some(role_id) => role_id,
+        None => {
+            let role = context
+                .http()
+                .create_role(guild_id)
+                .name(name.as_deref().unwrap_or(&hex(color)))
+                .color(color)
+                .permissions(Permissions::empty())
+                .reason("Automated color role creation")?
+                .exec()
+                .await?
+                .model()
+                .await?;
+
+            sqlx::query!(
+                "INSERT INTO roleme_roles (guild_id, id, color)
+                VALUES ($1, $2, $3);",
+                guild_id.to_string(),
+                role.id.to_string(),
+                hex(color),
+            )
+            .execute(context.postgres())
+            .await?;
+
+            role.id
+        }
+    };
+
+    let worn = worn(context, Some(role_id)).await?;
+
+    context
+        .http()
+        .add_guild_member_role(guild_id, context.message.author.id, role_id)
+        .reason("Automated color role grant")?
+        .exec()
+        .await?;
+
+    for role_id in worn.iter() {
+        context
+            .http()
+            .remove_guild_member_role(guild_id, context.message.author.id, *role_id)
+            .reason("Automated color role removal")?
+            .exec()
+            .await?;
+    }
+
+    drop_unused(context, guild_id, worn).await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn clear(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+    let worn = worn(context, None).await?;
+
+    if worn.is_empty() {
+        let reply = context.reply("You don't have a color role.").await?;
+        return Ok(Response::Message(reply));
+    }
+
+    for role_id in worn.iter() {
+        context
+            .http()
+            .remove_guild_member_role(guild_id, context.message.author.id, *role_id)
+            .reason("Automated color role removal")?
+            .exec()
+            .await?;
+    }
+
+    drop_unused(context, guild_id, worn).await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn cleanup(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let candidates = roleme::roles(context)
+        .await?
+        .into_iter()
+        .filter(|r| r.color.is_some())
+        .map(|r| r.id.0)
+        .collect();
+
+    let held = held(context, guild_id).await?;
+    let deleted = clean(context, guild_id, candidates, &held).await?;
+
+    let reply = context
+        .reply(match deleted {
+            0 => String::from("Every color role is still in use."),
+            1 => String::from("Deleted 1 unused color role."),
+            n => format!("Deleted {} unused color roles.", n),
+        })
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn show(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+    let palette = colors(context.postgres(), guild_id).await?;
+
+    if palette.is_empty() {
+        let reply = context
+            .reply("This server doesn't have a palette, so you can pick any color with `katze roleme color <color>`.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let swatches = palette
+        .iter()
+        .filter_map(|entry| args::color(&entry.color))
+        .map(rgba)
+        .collect::<Vec<Rgba<u8>>>();
+
+    let encoded = chart::encode(&chart::swatches(&swatches))?;
+    let attachment = AttachmentFile::from_bytes("palette.png", encoded.as_ref());
+
+    let lines = palette
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| match &entry.name {
+            Some(name) => format!("`{}` **{}** `{}`", index + 1, name, entry.color),
+            None => format!("`{}` `{}`", index + 1, entry.color),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let content = format!(
+        "**Colors you can pick with `katze roleme color`:**\n{}",
+        lines
+    );
+
+    let reply = context
+        .http()
+        .create_message(context.message.channel_id)
+        .content(&content)?
+        .attach(&[attachment])
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let (color, name) = match (
+        context.optional_arg::<ColorArg>().await?,
+        context.optional_rest::<String>().await?,
+    ) {
+        (Some(ColorArg(color)), name)
+            if color != 0 && name.as_ref().is_none_or(|n| n.chars().count() <= MAX_NAME) =>
+        {
+            (color, name)
+        }
+        _ => {
+            let reply = context.reply(ADD_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let count = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM roleme_palette WHERE (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_one(context.postgres())
+    .await?
+    .count;
+
+    if count >= MAX_PALETTE {
+        let reply = context
+            .reply(format!(
+                "The palette already has {} colors. Remove one first.",
+                MAX_PALETTE
+            ))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    // adding a color that's already there renames it
+    sqlx::query!(
+        "INSERT INTO roleme_palette (guild_id, color, name)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, color) DO UPDATE SET name = EXCLUDED.name;",
+        guild_id.to_string(),
+        hex(color),
+        name,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let color = match context.optional_arg::<ColorArg>().await? {
+        Some(ColorArg(color)) => color,
+        None => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    // members who already have the color keep it
+    let removed = sqlx::query!(
+        "DELETE FROM roleme_palette WHERE
+        (guild_id = $1 AND color = $2);",
+        guild_id.to_string(),
+        hex(color),
+    )
+    .execute(context.postgres())
+    .await?
+    .rows_affected();
+
+    if removed == 0 {
+        let reply = context.reply("That color isn't in the palette.").await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn palette(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("add") => add(context).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        _ => show(context).await,
+    }
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("palette") => palette(context).await,
+        Some("none") | Some("remove") => clear(context).await,
+        Some("cleanup") => cleanup(context).await,
+        Some(query) => pick(context, query).await,
+        None => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    args::RoleArg,
+    checks,
+    model::{GenericError, MessageContext, Response, ResponseReaction},
+    table::RolemeGroup,
+};
+use sqlx::{Error as SqlxError, PgPool};
+use twilight_model::{guild::Permissions, id::GuildId};
+
+const MAX_GROUPS: i64 = 25;
+const MAX_NAME: usize = 100;
+
+const USAGE: &str = "USAGE: katze roleme group [create | edit | delete | add | remove]";
+const CREATE_USAGE: &str =
+    "USAGE: katze roleme group create <name> [--exclusive] [--max <number>] [--requires <role>]";
+const EDIT_USAGE: &str = "USAGE: katze roleme group edit <name> [--exclusive | --inclusive] \
+    [--max <number> | --unlimited] [--requires <role> | --anyone]";
+const DELETE_USAGE: &str = "USAGE: katze roleme group delete <name>";
+const ADD_USAGE: &str = "USAGE: katze roleme group add <name> <role>";
+const REMOVE_USAGE: &str = "USAGE: katze roleme group remove <role>";
+
+pub async fn groups(postgres: &PgPool, guild_id: GuildId) -> Result<Vec<RolemeGroup>, SqlxError> {
+    sqlx::query_as!(
+        RolemeGroup,
+        "SELECT
+            id,
+            name,
+            exclusive,
+            max_picks,
+            required_role_id AS \"required_role_id: _\"
+        FROM roleme_groups WHERE
+        (guild_id = $1)
+        ORDER BY name;",
+        guild_id.to_string(),
+    )
+    .fetch_all(postgres)
+    .await
+}
+
+pub async fn by_id(
+    postgres: &PgPool,
+    guild_id: GuildId,
+    id: i32,
+) -> Result<Option<RolemeGroup>, SqlxError> {
+    sqlx::query_as!(
+        RolemeGroup,
+        "SELECT
+            id,
+            name,
+            exclusive,
+            max_picks,
+            required_role_id AS \"required_role_id: _\"
+        FROM roleme_groups WHERE
+        (guild_id = $1 AND id = $2);",
+        guild_id.to_string(),
+        id,
+    )
+    .fetch_optional(postgres)
+    .await
+}
+
+// group names are matched ignoring case
+async fn by_name(
+    postgres: &PgPool,
+    guild_id: GuildId,
+    name: &str,
+) -> Result<Option<RolemeGroup>, SqlxError> {
+    sqlx::query_as!(
+        RolemeGroup,
+        "SELECT
+            id,
+            name,
+            exclusive,
+            max_picks,
+            required_role_id AS \"required_role_id: _\"
+        FROM roleme_groups WHERE
+        (guild_id = $1 AND LOWER(name) = LOWER($2));",
+        guild_id.to_string(),
+        name,
+    )
+    .fetch_optional(postgres)
+    .await
+}
+
+/// Describe a group's rules, like "pick one, requires `Member`".
+pub fn rules(context: &MessageContext, group: &RolemeGroup) -> Vec<String> {
+    let mut rules = Vec::new();
+
+    if group.exclusive {
+        rules.push(String::from("pick one"));
+    } else if let Some(max) = group.max_picks {
+        rules.push(format!("up to {}", max));
+    }
+
+    if let Some(required) = &group.required_role_id {
+        if let Some(role) = context.cache().role(required.0) {
+            rules.push(format!("requires `{}`", role.name));
+        }
+    }
+
+    rules
+}
+
+async fn create(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let exclusive = context.flag("--exclusive");
+    let max = context.option::<i32>("--max").await?;
+    let required = context
+        .option::<RoleArg>("--requires")
+        .await?
+        .map(|RoleArg(role)| role.id.to_string());
+
+    let name = match context.optional_rest::<String>().await? {
+        Some(name) if name.chars().count() <= MAX_NAME && max.is_none_or(|max| max > 0) => name,
+        _ => {
+            let reply = context.reply(CREATE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    if by_name(context.postgres(), guild_id, &name)
+        .await?
+        .is_some()
+    {
+        let reply = context
+            .reply("There's already a group with that name.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let count = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM roleme_groups WHERE (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_one(context.postgres())
+    .await?
+    .count;
+
+    if count >= MAX_GROUPS {
+        let reply = context
+            .reply(format!(
+                "This server already has {} roleme groups. Delete one first.",
+                MAX_GROUPS
+            ))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    sqlx::query!(
+        "INSERT INTO roleme_groups (guild_id, name, exclusive, max_picks, required_role_id)
+        VALUES ($1, $2, $3, $4, $5);",
+        guild_id.to_string(),
+        name,
+        exclusive,
+        max,
+        required,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    let reply = context
+        .reply(format!(
+            "Created the group **{}**. Add roles to it with `katze roleme group add \"{}\" <role>`.",
+            name, name
+        ))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn edit(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let exclusive = context.flag("--exclusive");
+    let inclusive = context.flag("--inclusive");
+    let unlimited = context.flag("--unlimited");
+    let anyone = context.flag("--anyone");
+    let max = context.option::<i32>("--max").await?;
+    let required = context.option::<RoleArg>("--requires").await?;
+
+    let conflicting =
+        (exclusive && inclusive) || (unlimited && max.is_some()) || (anyone && required.is_some());
+    let valid = !conflicting && max.is_none_or(|max| max > 0);
+
+    let group = match context.optional_rest::<String>().await? {
+        Some(name) if valid => by_name(context.postgres(), guild_id, &name).await?,
+        _ => {
+            let reply = context.reply(EDIT_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let mut group = match group {
+        Some(group) => group,
+        None => {
+            let reply = context.reply("There's no group with that name.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    if exclusive || inclusive {
+        group.exclusive = exclusive;
+    }
+
+    if unlimited {
+        group.max_picks = None;
+    } else if max.is_some() {
+        group.max_picks = max;
+    }
+
+    let required = if anyone {
+        None
+    } else if let Some(RoleArg(role)) = required {
+        Some(role.id.to_string())
+    } else {
+        group.required_role_id.map(|role| role.to_string())
+    };
+
+    sqlx::query!(
+        "UPDATE roleme_groups SET
+        exclusive = $1, max_picks = $2, required_role_id = $3
+        WHERE (id = $4);",
+        group.exclusive,
+        group.max_picks,
+        required,
+        group.id,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn delete(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let group = match context.optional_rest::<String>().await? {
+        Some(name) => by_name(context.postgres(), guild_id, &name).await?,
+        None => {
+            let reply = context.reply(DELETE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    match group {
+        Some(group) => {
+            // the group's roles stay self-assignable, without a group
+            sqlx::query!("DELETE FROM roleme_groups WHERE (id = $1);", group.id)
+                .execute(context.postgres())
+                .await?;
+
+            context.react(&ResponseReaction::Success.value()).await?;
+
+            Ok(Response::Reaction)
+        }
+        None => {
+            let reply = context.reply("There's no group with that name.").await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
+
+async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let (name, role) = match (
+        context.optional_arg::<String>().await?,
+        context.optional_rest::<RoleArg>().await?,
+    ) {
+        (Some(name), Some(RoleArg(role))) => (name, role),
+        _ => {
+            let reply = context.reply(ADD_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let group = match by_name(context.postgres(), guild_id, &name).await? {
+        Some(group) => group,
+        None => {
+            let reply = context.reply("There's no group with that name.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    // a role can only be in one group, so this moves it out of any other
+    let updated = sqlx::query!(
+        "UPDATE roleme_roles SET group_id = $1 WHERE
+        (guild_id = $2 AND id = $3 AND color IS NULL);",
+        group.id,
+        guild_id.to_string(),
+        role.id.to_string(),
+    )
+    .execute(context.postgres())
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        let reply = context
+            .reply(
+                "That role isn't a roleme role. Enable it with `katze roleme enable <role>` first.",
+            )
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let role = match context.optional_rest::<RoleArg>().await? {
+        Some(RoleArg(role)) => role,
+        None => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let updated = sqlx::query!(
+        "UPDATE roleme_roles SET group_id = NULL WHERE
+        (guild_id = $1 AND id = $2 AND group_id IS NOT NULL);",
+        guild_id.to_string(),
+        role.id.to_string(),
+    )
+    .execute(context.postgres())
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        let reply = context.reply("That role isn't in a roleme group.").await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("create") => create(context).await,
+        Some("edit") => edit(context).await,
+        Some("delete") => delete(context).await,
+        Some("add") => add(context).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        _ => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
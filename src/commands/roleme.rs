@@ -1,17 +1,26 @@
 use crate::{
+    args::DurationArg,
+    checks,
+    commands::{rolecolor, rolegroup, rolemenu, rolepersist, temprole},
     error::KatzeError,
-    model::{GenericError, MessageContext, Response, ResponseReaction},
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
     table::RolemeRole,
 };
+use chrono::Duration;
 use twilight_http::request::AuditLogReason;
+use twilight_model::{
+    guild::{Permissions, Role},
+    id::RoleId,
+};
 
-async fn roles(context: &MessageContext) -> Result<Vec<RolemeRole>, GenericError> {
+pub async fn roles(context: &MessageContext) -> Result<Vec<RolemeRole>, GenericError> {
     let roles = sqlx::query_as!(
         RolemeRole,
         "SELECT
             guild_id AS \"guild_id: _\",
             id AS \"id: _\",
-            color
+            color,
+            group_id
         FROM roleme_roles WHERE
         (guild_id = $1);",
         context.message.guild_id.unwrap().to_string(),
@@ -43,54 +52,186 @@ async fn roles(context: &MessageContext) -> Result<Vec<RolemeRole>, GenericError
     Ok(roles)
 }
 
-async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let roles = roles(context).await?;
+// the roles named in the rest of the arguments, out of the given ones
+async fn named(
+    context: &mut MessageContext,
+    role_ids: impl IntoIterator<Item = RoleId>,
+) -> Result<Vec<Role>, GenericError> {
+    let candidates = role_ids
+        .into_iter()
+        .filter_map(|id| context.cache().role(id).map(|role| role.resource().clone()))
+        .collect::<Vec<Role>>();
+
+    let query = context.rest::<String>().await?;
+
+    context.find_roles(&query, &candidates).await
+}
+
+// give the author a roleme role, following the rules of its group. if it can't be given, this
+// returns why.
+async fn give(
+    context: &MessageContext,
+    roles: &[RolemeRole],
+    current: &mut Vec<RoleId>,
+    role: &Role,
+    duration: Option<Duration>,
+) -> Result<Option<String>, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
 
-    let maybe_id = context.message.mention_roles.first();
-    let name = context.args.join(" ");
+    // the role may have changed since it was enabled
+    if let Err(why) = checks::can_manage(context, role).await {
+        return Ok(Some(why.to_string()));
+    }
+
+    if role.permissions.intersects(checks::DANGEROUS) {
+        return Ok(Some(format!(
+            "`{}` has dangerous permissions now, so it can't be handed out.",
+            role.name
+        )));
+    }
 
-    let candidate = roles
+    let group = match roles
         .iter()
-        .filter_map(|r| context.cache().role(r.id.0))
-        .find(|role| {
-            if let Some(id) = maybe_id {
-                role.id == *id
-            } else {
-                role.name == name
-            }
+        .find(|r| r.id.0 == role.id)
+        .and_then(|r| r.group_id)
+    {
+        Some(id) => rolegroup::by_id(context.postgres(), guild_id, id).await?,
+        None => None,
+    };
+
+    let mut taken = Vec::new();
+
+    if let Some(group) = group {
+        // a prerequisite role that was deleted doesn't lock the group
+        let required = group.required_role_id.as_ref().and_then(|required| {
+            context
+                .cache()
+                .role(required.0)
+                .map(|role| (role.id, role.name.clone()))
         });
 
-    if let Some(role) = candidate {
+        if let Some((required_id, required_name)) = required {
+            if !current.contains(&required_id) {
+                return Ok(Some(format!(
+                    "You need the `{}` role to pick roles from **{}**.",
+                    required_name, group.name
+                )));
+            }
+        }
+
+        let picked = roles
+            .iter()
+            .filter(|r| r.group_id == Some(group.id) && r.id.0 != role.id)
+            .map(|r| r.id.0)
+            .filter(|id| current.contains(id))
+            .collect::<Vec<RoleId>>();
+
+        if group.exclusive {
+            taken = picked;
+        } else if let Some(max) = group.max_picks {
+            if picked.len() >= max as usize {
+                return Ok(Some(format!(
+                    "You can only have {} roles from **{}**. Take one away with `katze roleme remove <role>` first.",
+                    max, group.name
+                )));
+            }
+        }
+    }
+
+    context
+        .http()
+        .add_guild_member_role(guild_id, context.message.author.id, role.id)
+        .reason("Automated roleme role grant")?
+        .exec()
+        .await?;
+
+    for role_id in taken.iter() {
         context
             .http()
-            .add_guild_member_role(
-                context.message.guild_id.unwrap(),
-                context.message.author.id,
-                role.id,
-            )
-            .reason("Automated roleme role grant")?
+            .remove_guild_member_role(guild_id, context.message.author.id, *role_id)
+            .reason("Automated roleme role removal")?
             .exec()
             .await?;
+    }
+
+    // picking a role again without a duration keeps it for good
+    let author_id = context.message.author.id;
+
+    match duration {
+        Some(duration) => {
+            temprole::schedule(context.postgres(), guild_id, author_id, role.id, duration).await?;
+        }
+        None => {
+            temprole::cancel(context.postgres(), guild_id, author_id, role.id).await?;
+        }
+    }
+
+    current.retain(|id| !taken.contains(id));
+    current.push(role.id);
+
+    Ok(None)
+}
+
+async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let duration = context
+        .option::<DurationArg>("--for")
+        .await?
+        .map(|DurationArg(duration)| duration);
+
+    if duration.is_some_and(|duration| !temprole::valid(duration)) {
+        let reply = context
+            .reply("Temporary roles can last from a minute to a year.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let roles = roles(context).await?;
+
+    // color roles are picked with roleme color instead
+    let choices = roles
+        .iter()
+        .filter(|r| r.color.is_none())
+        .map(|r| r.id.0)
+        .collect::<Vec<RoleId>>();
+
+    let chosen = named(context, choices).await?;
+
+    let mut current = context.author_roles();
+    let mut refused = Vec::new();
 
+    for role in chosen.iter() {
+        if let Some(reason) = give(context, &roles, &mut current, role, duration).await? {
+            refused.push(reason);
+        }
+    }
+
+    if refused.len() < chosen.len() {
         context.react(&ResponseReaction::Success.value()).await?;
+    }
 
+    if refused.is_empty() {
         Ok(Response::Reaction)
     } else {
-        let reply = context
-            .reply("Couldn't find that role in the list of roleme roles.")
-            .await?;
+        let reply = context.reply(refused.join("\n")).await?;
 
         Ok(Response::Message(reply))
     }
 }
 
 async fn create(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let name = context.args.join(" ");
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
 
+    let name = context.rest::<String>().await?;
+
+    // without permissions, a new role would get the same ones as everyone
     let role = context
         .http()
         .create_role(context.message.guild_id.unwrap())
         .name(&name)
+        .permissions(Permissions::empty())
         .exec()
         .await?
         .model()
@@ -111,165 +252,178 @@ async fn create(context: &mut MessageContext) -> Result<Response, GenericError>
 }
 
 async fn disable(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let roles = roles(context).await?;
-
-    let maybe_id = context.message.mention_roles.first();
-    let name = context.args.join(" ");
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
 
-    let candidate = roles
-        .iter()
-        .filter_map(|r| context.cache().role(r.id.0))
-        .find(|role| {
-            if let Some(id) = maybe_id {
-                role.id == *id
-            } else {
-                role.name == name
-            }
-        });
+    let roles = roles(context).await?;
+    let chosen = named(context, roles.into_iter().map(|r| r.id.0)).await?;
 
-    if let Some(role) = candidate {
+    for role in chosen {
         sqlx::query!(
             "DELETE FROM roleme_roles WHERE id = $1;",
             role.id.to_string(),
         )
         .execute(context.postgres())
         .await?;
+    }
 
-        context.react(&ResponseReaction::Success.value()).await?;
-
-        Ok(Response::Reaction)
-    } else {
-        let reply = context
-            .reply("This role wasn't found in the list of roleme roles.")
-            .await?;
+    context.react(&ResponseReaction::Success.value()).await?;
 
-        Ok(Response::Message(reply))
-    }
+    Ok(Response::Reaction)
 }
 
 async fn enable(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let maybe_id = context.message.mention_roles.first();
-    let name = context.args.join(" ");
+    let guild_id = context.message.guild_id.unwrap();
 
-    let roles = context
-        .http()
-        .roles(context.message.guild_id.unwrap())
-        .exec()
-        .await?
-        .models()
-        .await?;
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
 
-    let candidate = roles.iter().find(|r| {
-        if let Some(id) = maybe_id {
-            r.id == *id
-        } else {
-            r.name == name
-        }
-    });
+    let role_ids = context
+        .cache()
+        .guild_roles(guild_id)
+        .map(|roles| roles.iter().copied().collect::<Vec<RoleId>>())
+        .unwrap_or_default();
 
-    if let Some(role) = candidate {
+    let chosen = named(context, role_ids).await?;
+
+    // check every role before enabling any of them
+    for role in chosen.iter() {
+        checks::can_hand_out(context, role).await?;
+    }
+
+    for role in chosen {
         sqlx::query!(
             "INSERT INTO roleme_roles (guild_id, id)
-            VALUES ($1, $2);",
-            context.message.guild_id.unwrap().to_string(),
+            VALUES ($1, $2)
+            ON CONFLICT (id) DO NOTHING;",
+            guild_id.to_string(),
             role.id.to_string(),
         )
         .execute(context.postgres())
         .await?;
+    }
 
-        context.react(&ResponseReaction::Success.value()).await?;
-
-        Ok(Response::Reaction)
-    } else {
-        let reply = context
-            .reply("This role wasn't found in the server.")
-            .await?;
+    context.react(&ResponseReaction::Success.value()).await?;
 
-        Ok(Response::Message(reply))
-    }
+    Ok(Response::Reaction)
 }
 
 async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let roles = roles(context).await?;
+    let guild_id = context.message.guild_id.unwrap();
 
-    let maybe_id = context.message.mention_roles.first();
-    let name = context.args.join(" ");
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
 
-    let candidate = roles
-        .iter()
-        .filter_map(|r| context.cache().role(r.id.0))
-        .find(|role| {
-            if let Some(id) = maybe_id {
-                role.id == *id
-            } else {
-                role.name == name
-            }
-        });
+    let roles = roles(context).await?;
+    let chosen = named(context, roles.into_iter().map(|r| r.id.0)).await?;
 
-    if let Some(role) = candidate {
+    for role in chosen.iter() {
+        checks::can_manage(context, role).await?;
+    }
+
+    for role in chosen {
         context
             .http()
-            .remove_guild_member_role(
-                context.message.guild_id.unwrap(),
-                context.message.author.id,
-                role.id,
-            )
+            .remove_guild_member_role(guild_id, context.message.author.id, role.id)
             .reason("Automated roleme role removal")?
             .exec()
             .await?;
 
-        context.react(&ResponseReaction::Success.value()).await?;
+        temprole::cancel(
+            context.postgres(),
+            guild_id,
+            context.message.author.id,
+            role.id,
+        )
+        .await?;
+    }
 
-        Ok(Response::Reaction)
-    } else {
-        let reply = context
-            .reply("Couldn't find that role in the list of roleme roles.")
-            .await?;
+    context.react(&ResponseReaction::Success.value()).await?;
 
-        Ok(Response::Message(reply))
-    }
+    Ok(Response::Reaction)
 }
 
 async fn list(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let roles = roles(context).await?;
+    let guild_id = context.message.guild_id.unwrap();
+
+    // color roles are listed with roleme color palette instead
+    let roles = roles(context)
+        .await?
+        .into_iter()
+        .filter(|r| r.color.is_none())
+        .collect::<Vec<RolemeRole>>();
 
     if roles.is_empty() {
         let reply = context
             .reply("There aren't any roleme roles in this server.")
             .await?;
 
-        Ok(Response::Message(reply))
-    } else {
-        let roles_fmt = roles
-            .into_iter()
+        return Ok(Response::Message(reply));
+    }
+
+    let groups = rolegroup::groups(context.postgres(), guild_id).await?;
+
+    let names = |group_id: Option<i32>| {
+        roles
+            .iter()
+            .filter(|r| r.group_id == group_id)
             .filter_map(|r| context.cache().role(r.id.0))
             .map(|r| format!("* `{}`", r.name))
             .collect::<Vec<String>>()
-            .join("\n");
+    };
 
-        let reply = context
-            .reply(format!(
-                "Here's a list of roles you can give yourself in this server:\n{}",
-                roles_fmt
-            ))
-            .await?;
+    // each group is one entry, so pages don't split a group apart
+    let mut sections = Vec::new();
 
-        Ok(Response::Message(reply))
+    for group in groups.iter() {
+        let names = names(Some(group.id));
+
+        if names.is_empty() {
+            continue;
+        }
+
+        let rules = rolegroup::rules(context, group);
+        let header = if rules.is_empty() {
+            format!("**{}**", group.name)
+        } else {
+            format!("**{}** ({})", group.name, rules.join(", "))
+        };
+
+        sections.push(format!("{}\n{}", header, names.join("\n")));
     }
+
+    let ungrouped = names(None);
+
+    if !ungrouped.is_empty() {
+        if sections.is_empty() {
+            sections.push(ungrouped.join("\n"));
+        } else {
+            sections.push(format!("**Other**\n{}", ungrouped.join("\n")));
+        }
+    }
+
+    let reply = context
+        .paginate(model::pages(
+            "Here's a list of roles you can give yourself in this server:",
+            sections,
+        ))
+        .await?;
+
+    Ok(Response::Message(reply))
 }
 
 pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
-    if let Some(command) = context.next() {
-        match command.as_ref() {
-            "add" => add(context).await,
-            "create" => create(context).await,
-            "disable" => disable(context).await,
-            "enable" => enable(context).await,
-            "remove" => remove(context).await,
-            "list" => list(context).await,
-            _ => add(context).await,
-        }
-    } else {
-        add(context).await
+    match context.subcommand_of(&[
+        "add", "create", "disable", "enable", "remove", "list", "color", "colour", "group", "menu",
+        "persist",
+    ]) {
+        Some("create") => create(context).await,
+        Some("disable") => disable(context).await,
+        Some("enable") => enable(context).await,
+        Some("remove") => remove(context).await,
+        Some("list") => list(context).await,
+        Some("color") | Some("colour") => rolecolor::execute(context).await,
+        Some("group") => rolegroup::execute(context).await,
+        Some("menu") => rolemenu::execute(context).await,
+        Some("persist") => rolepersist::execute(context).await,
+        // anything else is the name of a role to add
+        _ => add(context).await,
     }
 }
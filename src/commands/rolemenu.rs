@@ -0,0 +1,492 @@
+use crate::{
+    args::{ChannelArg, EmojiArg, RoleArg},
+    checks,
+    emoji::{self, ParsedEmoji},
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
+    table::{RoleMenu, RoleMenuEntry},
+};
+use sqlx::{Error as SqlxError, PgPool};
+use twilight_cache_inmemory::InMemoryCache;
+use twilight_model::{
+    application::component::{button::ButtonStyle, ActionRow, Button, Component},
+    channel::ReactionType,
+    guild::Permissions,
+    id::{GuildId, MessageId, RoleId},
+};
+
+// discord allows 5 rows of 5 buttons, and 20 different reactions on a message
+const MAX_BUTTONS: usize = 25;
+const MAX_REACTIONS: usize = 20;
+const MAX_TITLE: usize = 200;
+const PER_ROW: usize = 5;
+
+/// Prefix of the custom id of every role menu button, followed by the menu and role ids.
+pub const CUSTOM_ID: &str = "rolemenu";
+
+const USAGE: &str = "USAGE: katze roleme menu [create | add | remove | delete | list]";
+const CREATE_USAGE: &str =
+    "USAGE: katze roleme menu create <channel> <title> [--exclusive] [--reactions]";
+const ADD_USAGE: &str = "USAGE: katze roleme menu add <id> <role> [--emoji <emoji>]";
+const REMOVE_USAGE: &str = "USAGE: katze roleme menu remove <id> <role>";
+const DELETE_USAGE: &str = "USAGE: katze roleme menu delete <id>";
+
+pub async fn by_id(
+    postgres: &PgPool,
+    guild_id: GuildId,
+    id: i32,
+) -> Result<Option<RoleMenu>, SqlxError> {
+    sqlx::query_as!(
+        RoleMenu,
+        "SELECT
+            id,
+            channel_id AS \"channel_id: _\",
+            message_id AS \"message_id: _\",
+            title,
+            exclusive,
+            reactions
+        FROM role_menus WHERE
+        (guild_id = $1 AND id = $2);",
+        guild_id.to_string(),
+        id,
+    )
+    .fetch_optional(postgres)
+    .await
+}
+
+pub async fn by_message(
+    postgres: &PgPool,
+    message_id: MessageId,
+) -> Result<Option<RoleMenu>, SqlxError> {
+    sqlx::query_as!(
+        RoleMenu,
+        "SELECT
+            id,
+            channel_id AS \"channel_id: _\",
+            message_id AS \"message_id: _\",
+            title,
+            exclusive,
+            reactions
+        FROM role_menus WHERE
+        (message_id = $1);",
+        message_id.to_string(),
+    )
+    .fetch_optional(postgres)
+    .await
+}
+
+pub async fn entries(postgres: &PgPool, menu_id: i32) -> Result<Vec<RoleMenuEntry>, SqlxError> {
+    sqlx::query_as!(
+        RoleMenuEntry,
+        "SELECT
+            role_id AS \"role_id: _\",
+            emoji
+        FROM role_menu_entries WHERE
+        (menu_id = $1)
+        ORDER BY position;",
+        menu_id,
+    )
+    .fetch_all(postgres)
+    .await
+}
+
+impl RoleMenuEntry {
+    pub fn parsed(&self) -> Option<ParsedEmoji> {
+        self.emoji.as_deref().and_then(emoji::parse_one)
+    }
+}
+
+fn role_name(cache: &InMemoryCache, role_id: RoleId) -> String {
+    cache
+        .role(role_id)
+        .map(|role| role.name.clone())
+        .unwrap_or_else(|| String::from("deleted role"))
+}
+
+// the text of a menu, and its buttons unless it uses reactions
+fn render(
+    cache: &InMemoryCache,
+    menu: &RoleMenu,
+    entries: &[RoleMenuEntry],
+) -> (String, Vec<Component>) {
+    let mut content = format!("**{}**\n", menu.title);
+
+    for entry in entries.iter() {
+        let name = role_name(cache, entry.role_id.0);
+
+        match &entry.emoji {
+            Some(emoji) => content.push_str(&format!("{} {}\n", emoji, name)),
+            None => content.push_str(&format!("{}\n", name)),
+        }
+    }
+
+    if entries.is_empty() {
+        content.push_str("There aren't any roles in this menu yet.");
+    } else {
+        let how = if menu.reactions { "React" } else { "Click" };
+        let pick = if menu.exclusive {
+            "You can only have one of these roles."
+        } else {
+            "Pick as many as you like."
+        };
+
+        content.push_str(&format!("\n{} to toggle a role. {}", how, pick));
+    }
+
+    if menu.reactions {
+        return (content, Vec::new());
+    }
+
+    let buttons = entries
+        .iter()
+        .map(|entry| {
+            Component::Button(Button {
+                custom_id: Some(format!("{}:{}:{}", CUSTOM_ID, menu.id, entry.role_id)),
+                disabled: false,
+                emoji: entry.parsed().as_ref().map(ReactionType::from),
+                label: Some(role_name(cache, entry.role_id.0).chars().take(80).collect()),
+                style: ButtonStyle::Secondary,
+                url: None,
+            })
+        })
+        .collect::<Vec<Component>>();
+
+    let rows = buttons
+        .chunks(PER_ROW)
+        .map(|row| {
+            Component::ActionRow(ActionRow {
+                components: row.to_vec(),
+            })
+        })
+        .collect();
+
+    (content, rows)
+}
+
+// bring the posted message up to date with the menu
+async fn refresh(context: &MessageContext, menu: &RoleMenu) -> Result<(), GenericError> {
+    let entries = entries(context.postgres(), menu.id).await?;
+    let (content, components) = render(context.cache(), menu, &entries);
+
+    context
+        .http()
+        .update_message(menu.channel_id.0, menu.message_id.0)
+        .content(Some(&content))?
+        .components(Some(&components))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+async fn create(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let exclusive = context.flag("--exclusive");
+    let reactions = context.flag("--reactions");
+
+    let (channel, title) = match (
+        context.optional_arg::<ChannelArg>().await?,
+        context.optional_rest::<String>().await?,
+    ) {
+        (Some(ChannelArg(channel)), Some(title)) if title.chars().count() <= MAX_TITLE => {
+            (channel, title)
+        }
+        _ => {
+            let reply = context.reply(CREATE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    // the menu is posted in another channel, so the bot needs to be able to post there
+    let mut needed = Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES;
+
+    if reactions {
+        needed |= Permissions::ADD_REACTIONS;
+    }
+
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+    checks::bot_has_permission_in(context, channel.id(), needed).await?;
+
+    let content = format!("**{}**\nThere aren't any roles in this menu yet.", title);
+
+    let posted = context
+        .http()
+        .create_message(channel.id())
+        .content(&content)?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    let id = sqlx::query!(
+        "INSERT INTO role_menus (guild_id, channel_id, message_id, title, exclusive, reactions)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id;",
+        guild_id.to_string(),
+        channel.id().to_string(),
+        posted.id.to_string(),
+        title,
+        exclusive,
+        reactions,
+    )
+    .fetch_one(context.postgres())
+    .await?
+    .id;
+
+    let reply = context
+        .reply(format!(
+            "Created menu `{}` in <#{}>. Add roles to it with `katze roleme menu add {} <role>`.",
+            id,
+            channel.id(),
+            id
+        ))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let emoji = context
+        .option::<EmojiArg>("--emoji")
+        .await?
+        .map(|EmojiArg(emoji)| emoji);
+
+    let (id, role) = match (
+        context.optional_arg::<i32>().await?,
+        context.optional_rest::<RoleArg>().await?,
+    ) {
+        (Some(id), Some(RoleArg(role))) => (id, role),
+        _ => {
+            let reply = context.reply(ADD_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let menu = match by_id(context.postgres(), guild_id, id).await? {
+        Some(menu) => menu,
+        None => {
+            let reply = context.reply("There's no role menu with that id.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::can_hand_out(context, &role).await?;
+
+    let entries = entries(context.postgres(), menu.id).await?;
+    let limit = if menu.reactions {
+        MAX_REACTIONS
+    } else {
+        MAX_BUTTONS
+    };
+
+    let problem = if entries.iter().any(|entry| entry.role_id.0 == role.id) {
+        Some(String::from("That role is already in the menu."))
+    } else if entries.len() >= limit {
+        Some(format!("Menus can have at most {} roles.", limit))
+    } else if menu.reactions && emoji.is_none() {
+        Some(String::from(
+            "Reaction menus need an emoji for each role, given with `--emoji`.",
+        ))
+    } else if emoji.is_some() && entries.iter().any(|entry| entry.parsed() == emoji) {
+        Some(String::from("That emoji is already used in the menu."))
+    } else {
+        None
+    };
+
+    if let Some(problem) = problem {
+        let reply = context.reply(problem).await?;
+        return Ok(Response::Message(reply));
+    }
+
+    sqlx::query!(
+        "INSERT INTO role_menu_entries (menu_id, role_id, emoji, position)
+        VALUES ($1, $2, $3, $4);",
+        menu.id,
+        role.id.to_string(),
+        emoji.as_ref().map(ParsedEmoji::to_string),
+        entries.len() as i32,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    refresh(context, &menu).await?;
+
+    if let (true, Some(emoji)) = (menu.reactions, &emoji) {
+        context
+            .http()
+            .create_reaction(menu.channel_id.0, menu.message_id.0, &emoji.request())
+            .exec()
+            .await?;
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let (id, role) = match (
+        context.optional_arg::<i32>().await?,
+        context.optional_rest::<RoleArg>().await?,
+    ) {
+        (Some(id), Some(RoleArg(role))) => (id, role),
+        _ => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let menu = match by_id(context.postgres(), guild_id, id).await? {
+        Some(menu) => menu,
+        None => {
+            let reply = context.reply("There's no role menu with that id.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let removed = sqlx::query!(
+        "DELETE FROM role_menu_entries WHERE
+        (menu_id = $1 AND role_id = $2)
+        RETURNING emoji;",
+        menu.id,
+        role.id.to_string(),
+    )
+    .fetch_optional(context.postgres())
+    .await?;
+
+    let removed = match removed {
+        Some(removed) => removed,
+        None => {
+            let reply = context.reply("That role isn't in the menu.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    // the positions are only used for ordering, so gaps don't matter
+    refresh(context, &menu).await?;
+
+    if let (true, Some(emoji)) = (
+        menu.reactions,
+        removed.emoji.as_deref().and_then(emoji::parse_one),
+    ) {
+        context
+            .http()
+            .delete_all_reaction(menu.channel_id.0, menu.message_id.0, &emoji.request())
+            .exec()
+            .await?;
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn delete(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let menu = match context.optional_arg::<i32>().await? {
+        Some(id) => by_id(context.postgres(), guild_id, id).await?,
+        None => {
+            let reply = context.reply(DELETE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let menu = match menu {
+        Some(menu) => menu,
+        None => {
+            let reply = context.reply("There's no role menu with that id.").await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    sqlx::query!("DELETE FROM role_menus WHERE (id = $1);", menu.id)
+        .execute(context.postgres())
+        .await?;
+
+    // the message may already be gone
+    let _ = context
+        .http()
+        .delete_message(menu.channel_id.0, menu.message_id.0)
+        .exec()
+        .await;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn list(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let menus = sqlx::query!(
+        "SELECT
+            role_menus.id,
+            role_menus.channel_id,
+            role_menus.title,
+            role_menus.exclusive,
+            role_menus.reactions,
+            COUNT(role_menu_entries.role_id) AS \"roles!\"
+        FROM role_menus
+        LEFT JOIN role_menu_entries ON (role_menu_entries.menu_id = role_menus.id)
+        WHERE (role_menus.guild_id = $1)
+        GROUP BY role_menus.id
+        ORDER BY role_menus.id;",
+        guild_id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    if menus.is_empty() {
+        let reply = context
+            .reply("There aren't any role menus in this server.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let lines = menus.into_iter().map(|menu| {
+        format!(
+            "`{}` **{}** in <#{}>: {} roles, {}{}",
+            menu.id,
+            menu.title,
+            menu.channel_id,
+            menu.roles,
+            if menu.reactions {
+                "reactions"
+            } else {
+                "buttons"
+            },
+            if menu.exclusive { ", exclusive" } else { "" },
+        )
+    });
+
+    let reply = context.paginate(model::pages("Role menus:", lines)).await?;
+
+    Ok(Response::Message(reply))
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("create") => create(context).await,
+        Some("add") => add(context).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        Some("delete") => delete(context).await,
+        Some("list") | Some("ls") | None => list(context).await,
+        Some(_) => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{
+    args::RoleArg,
+    checks,
+    model::{GenericError, MessageContext, Response, ResponseReaction},
+    table::{id::SqlxRoleId, Setting},
+};
+use twilight_model::guild::Permissions;
+
+const USAGE: &str = "USAGE: katze roleme persist [on | off | add | remove]";
+const ADD_USAGE: &str = "USAGE: katze roleme persist add <role>";
+const REMOVE_USAGE: &str = "USAGE: katze roleme persist remove <role>";
+
+async fn status(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+    let setting = Setting::query(context.postgres().clone(), guild_id).await?;
+
+    let extra = sqlx::query!(
+        "SELECT role_id AS \"role_id: SqlxRoleId\" FROM persistent_roles WHERE
+        (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?
+    .into_iter()
+    .filter_map(|row| context.cache().role(row.role_id.0))
+    .map(|role| format!("`{}`", role.name))
+    .collect::<Vec<String>>();
+
+    let mut content = String::from(if setting.roleme_persist {
+        "Members who leave and rejoin get their roleme roles back."
+    } else {
+        "Members who leave and rejoin don't get their roles back."
+    });
+
+    if !extra.is_empty() {
+        content.push_str(&format!(" Also kept: {}.", extra.join(", ")));
+    }
+
+    let reply = context.reply(content).await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn toggle(context: &MessageContext, enabled: bool) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    sqlx::query!(
+        "UPDATE settings SET roleme_persist = $2 WHERE (guild_id = $1);",
+        guild_id.to_string(),
+        enabled,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let role = match context.optional_rest::<RoleArg>().await? {
+        Some(RoleArg(role)) => role,
+        None => {
+            let reply = context.reply(ADD_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::can_hand_out(context, &role).await?;
+
+    sqlx::query!(
+        "INSERT INTO persistent_roles (guild_id, role_id)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING;",
+        guild_id.to_string(),
+        role.id.to_string(),
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let role = match context.optional_rest::<RoleArg>().await? {
+        Some(RoleArg(role)) => role,
+        None => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let removed = sqlx::query!(
+        "DELETE FROM persistent_roles WHERE
+        (guild_id = $1 AND role_id = $2);",
+        guild_id.to_string(),
+        role.id.to_string(),
+    )
+    .execute(context.postgres())
+    .await?
+    .rows_affected();
+
+    if removed == 0 {
+        let reply = context
+            .reply("That role isn't kept for members who rejoin.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("on") => toggle(context, true).await,
+        Some("off") => toggle(context, false).await,
+        Some("add") => add(context).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        Some(_) => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+        None => status(context).await,
+    }
+}
@@ -96,7 +96,9 @@ pub async fn count(context: &MessageContext) -> Result<Response, GenericError> {
 }
 
 pub async fn delete(context: &mut MessageContext) -> Result<Response, GenericError> {
-    if let Some(message_id) = context.next() {
+    if let Some(message_id) = context.optional_arg::<u64>().await? {
+        let message_id = message_id.to_string();
+
         let image = sqlx::query_as!(
             Image,
             "DELETE FROM images WHERE
@@ -251,7 +253,9 @@ pub async fn pick(context: &mut MessageContext) -> Result<Response, GenericError
     let guild_id = context.message.guild_id.unwrap();
     let now = Utc::now();
 
-    if let Some(message_id) = context.next() {
+    if let Some(message_id) = context.optional_arg::<u64>().await? {
+        let message_id = message_id.to_string();
+
         let image = sqlx::query_as!(
             Image,
             "SELECT
@@ -387,7 +391,9 @@ async fn rotate(context: &MessageContext) -> Result<Response, GenericError> {
 }
 
 pub async fn show(context: &mut MessageContext) -> Result<Response, GenericError> {
-    if let Some(message_id) = context.next() {
+    if let Some(message_id) = context.optional_arg::<u64>().await? {
+        let message_id = message_id.to_string();
+
         let image = sqlx::query_as!(
             Image,
             "SELECT
@@ -433,17 +439,14 @@ pub async fn show(context: &mut MessageContext) -> Result<Response, GenericError
 }
 
 pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
-    if let Some(command) = context.next() {
-        match command.as_ref() {
-            "add_image" | "pls" => add_image(context).await,
-            "count" => count(context).await,
-            "delete" | "remove" | "rm" => delete(context).await,
-            "list" | "ls" => list(context).await,
-            "pick" => pick(context).await,
-            "show" => show(context).await,
-            _ => Ok(Response::None),
-        }
-    } else {
-        rotate(context).await
+    match context.subcommand().as_deref() {
+        Some("add_image") | Some("pls") => add_image(context).await,
+        Some("count") => count(context).await,
+        Some("delete") | Some("remove") | Some("rm") => delete(context).await,
+        Some("list") | Some("ls") => list(context).await,
+        Some("pick") => pick(context).await,
+        Some("show") => show(context).await,
+        Some(_) => Ok(Response::None),
+        None => rotate(context).await,
     }
 }
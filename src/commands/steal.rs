@@ -0,0 +1,446 @@
+use crate::{
+    cdn, checks,
+    emoji::{self, ParsedEmoji},
+    model::{GenericError, MessageContext, Response, ResponseReaction},
+};
+use http::uri::Uri;
+use hyper::{
+    body::{self, Body},
+    Request,
+};
+use image::{
+    codecs::gif::{GifDecoder, GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    AnimationDecoder, DynamicImage, Frame, GenericImageView, ImageFormat, ImageOutputFormat,
+};
+use std::{cmp, convert::TryFrom, fmt::Write};
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::{
+    channel::message::sticker::{MessageSticker, StickerFormatType},
+    guild::{Emoji, Permissions, PremiumTier},
+    id::GuildId,
+};
+
+pub const EMOJI_LIMIT: usize = 256 * 1024;
+const MAX_ITEMS: usize = 10;
+const MIN_SIZE: u32 = 32;
+const STICKER_LIMIT: usize = 512 * 1024;
+const USAGE: &str = "USAGE: katze steal <emoji or link>... [<name>...]
+Reply to a message to steal its emojis and stickers instead.";
+
+#[derive(Debug)]
+enum Kind {
+    Emoji { animated: bool },
+    // a link to an image, which can only be told apart from an animation once it's downloaded
+    Link,
+    Sticker { sticker: MessageSticker },
+}
+
+#[derive(Debug)]
+struct Item {
+    kind: Kind,
+    uri: Uri,
+    name: String,
+}
+
+impl Item {
+    fn emoji(emoji: ParsedEmoji) -> Option<Self> {
+        match emoji {
+            ParsedEmoji::Custom { animated, name, id } => {
+                let format = if animated { "gif" } else { "png" };
+
+                Some(Self {
+                    kind: Kind::Emoji { animated },
+                    uri: Uri::try_from(cdn::emoji(id, format)).ok()?,
+                    name,
+                })
+            }
+            ParsedEmoji::Unicode(_) => None,
+        }
+    }
+
+    fn sticker(sticker: &MessageSticker) -> Option<Self> {
+        Some(Self {
+            kind: Kind::Sticker {
+                sticker: sticker.clone(),
+            },
+            uri: Uri::try_from(cdn::sticker(sticker.id)).ok()?,
+            name: sticker.name.clone(),
+        })
+    }
+}
+
+/// How many more emojis and stickers a guild can hold. Static and animated emojis have separate
+/// limits, and managed emojis from integrations don't count against either.
+#[derive(Debug)]
+pub struct Slots {
+    pub stills: usize,
+    pub animated: usize,
+    pub stickers: usize,
+}
+
+impl Slots {
+    pub fn free(context: &MessageContext, guild_id: GuildId) -> Self {
+        let tier = context
+            .cache()
+            .guild(guild_id)
+            .map(|guild| guild.premium_tier())
+            .unwrap_or_default();
+
+        // emoji slots for static and animated emojis each, and sticker slots, by boost tier
+        let (emoji_slots, sticker_slots) = match tier {
+            PremiumTier::None => (50, 5),
+            PremiumTier::Tier1 => (100, 15),
+            PremiumTier::Tier2 => (150, 30),
+            PremiumTier::Tier3 => (250, 60),
+        };
+
+        let (stills, animated) = context
+            .cache()
+            .guild_emojis(guild_id)
+            .map(|emojis| {
+                emojis
+                    .iter()
+                    .filter_map(|id| context.cache().emoji(*id))
+                    .filter(|emoji| !emoji.managed())
+                    .fold((0, 0), |(stills, animated), emoji| {
+                        if emoji.animated() {
+                            (stills, animated + 1)
+                        } else {
+                            (stills + 1, animated)
+                        }
+                    })
+            })
+            .unwrap_or_default();
+
+        let stickers = context
+            .cache()
+            .guild_stickers(guild_id)
+            .map(|stickers| stickers.len())
+            .unwrap_or_default();
+
+        Self {
+            stills: emoji_slots - cmp::min(stills, emoji_slots),
+            animated: emoji_slots - cmp::min(animated, emoji_slots),
+            stickers: sticker_slots - cmp::min(stickers, sticker_slots),
+        }
+    }
+}
+
+fn mime(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Gif => "image/gif",
+        ImageFormat::Jpeg => "image/jpeg",
+        _ => "image/png",
+    }
+}
+
+// scale an animation down by shrinking every frame, keeping the delays
+fn scale_gif(buffer: &[u8], width: u32, height: u32) -> Result<Vec<u8>, GenericError> {
+    let frames = GifDecoder::new(buffer)?.into_frames().collect_frames()?;
+    let frames = frames.into_iter().map(|frame| {
+        let delay = frame.delay();
+        let resized = imageops::resize(frame.buffer(), width, height, FilterType::Triangle);
+        Frame::from_parts(resized, 0, 0, delay)
+    });
+
+    let mut encoded = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut encoded);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames)?;
+    }
+
+    Ok(encoded)
+}
+
+// discord rejects images over the upload limit, so scale them down by a quarter at a time until
+// they fit. animations stay animated, and everything else becomes a png.
+fn shrink(buffer: Vec<u8>, limit: usize) -> Result<(Vec<u8>, ImageFormat), GenericError> {
+    let format = image::guess_format(&buffer)?;
+
+    if buffer.len() <= limit && matches!(format, ImageFormat::Png | ImageFormat::Gif) {
+        return Ok((buffer, format));
+    }
+
+    let image = image::load_from_memory(&buffer)?;
+    let (mut width, mut height) = (image.width(), image.height());
+
+    loop {
+        let encoded = if format == ImageFormat::Gif {
+            scale_gif(&buffer, width, height)?
+        } else {
+            let resized = if (width, height) == (image.width(), image.height()) {
+                image.clone()
+            } else {
+                image.resize_exact(width, height, FilterType::Triangle)
+            };
+
+            let mut encoded = Vec::new();
+            DynamicImage::ImageRgba8(resized.to_rgba8())
+                .write_to(&mut encoded, ImageOutputFormat::Png)?;
+            encoded
+        };
+
+        if encoded.len() <= limit {
+            let format = if format == ImageFormat::Gif {
+                ImageFormat::Gif
+            } else {
+                ImageFormat::Png
+            };
+
+            return Ok((encoded, format));
+        }
+
+        if width.min(height) * 3 / 4 < MIN_SIZE {
+            return Err(format!("still over {} KiB at the smallest size", limit / 1024).into());
+        }
+
+        width = width * 3 / 4;
+        height = height * 3 / 4;
+    }
+}
+
+pub async fn fetch(context: &MessageContext, uri: Uri) -> Result<Vec<u8>, GenericError> {
+    let request = Request::get(uri).body(Body::empty())?;
+    let mut response = context.hyper().request(request).await?;
+
+    if !response.status().is_success() {
+        return Err(format!("the image couldn't be downloaded ({})", response.status()).into());
+    }
+
+    Ok(body::to_bytes(response.body_mut()).await?.to_vec())
+}
+
+/// Upload an emoji to the guild the command was used in, scaling it down if it's too large.
+pub async fn create_emoji(
+    context: &MessageContext,
+    name: &str,
+    buffer: Vec<u8>,
+) -> Result<Emoji, GenericError> {
+    if !emoji::valid_name(name) {
+        return Err("emoji names must be 2 to 32 letters, numbers, or underscores".into());
+    }
+
+    let (buffer, format) = shrink(buffer, EMOJI_LIMIT)?;
+    let image = format!("data:{};base64,{}", mime(format), base64::encode(buffer));
+
+    let emoji = context
+        .http()
+        .create_emoji(context.message.guild_id.unwrap(), name, image.as_str())
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    Ok(emoji)
+}
+
+async fn upload(
+    context: &MessageContext,
+    item: &Item,
+    buffer: Vec<u8>,
+) -> Result<String, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    match &item.kind {
+        Kind::Emoji { .. } | Kind::Link => {
+            let emoji = create_emoji(context, &item.name, buffer).await?;
+
+            context
+                .react(&RequestReactionType::Custom {
+                    id: emoji.id,
+                    name: Some(emoji.name.as_str()),
+                })
+                .await?;
+
+            let parsed = ParsedEmoji::Custom {
+                animated: emoji.animated,
+                name: emoji.name,
+                id: emoji.id,
+            };
+
+            Ok(parsed.to_string())
+        }
+        Kind::Sticker { sticker } => {
+            // apngs can't be scaled without losing their animation
+            let buffer = match sticker.format_type {
+                StickerFormatType::Apng if buffer.len() > STICKER_LIMIT => {
+                    return Err(format!(
+                        "animated stickers must be under {} KiB",
+                        STICKER_LIMIT / 1024
+                    )
+                    .into());
+                }
+                StickerFormatType::Apng => buffer,
+                _ => shrink(buffer, STICKER_LIMIT)?.0,
+            };
+
+            // the sticker's tags and description are only available from the api
+            let original = context
+                .http()
+                .sticker(sticker.id)
+                .exec()
+                .await?
+                .model()
+                .await?;
+            let description = original
+                .description
+                .filter(|description| description.chars().count() >= 2)
+                .unwrap_or_else(|| item.name.clone());
+            let tags = if original.tags.chars().count() >= 2 {
+                original.tags
+            } else {
+                item.name.clone()
+            };
+
+            let created = context
+                .http()
+                .create_guild_sticker(guild_id, &item.name, &description, &tags, &buffer)?
+                .exec()
+                .await?
+                .model()
+                .await?;
+
+            Ok(format!("the sticker `{}`", created.name))
+        }
+    }
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    // every argument is either something to steal, or a name for it
+    let mut items = Vec::new();
+    let mut names = Vec::new();
+
+    while let Some(arg) = context.optional_arg::<String>().await? {
+        if let Ok(uri) = Uri::try_from(&arg) {
+            if uri.scheme().is_some() {
+                items.push(Item {
+                    kind: Kind::Link,
+                    uri,
+                    name: String::from("emoji"),
+                });
+
+                continue;
+            }
+        }
+
+        let parsed = emoji::parse(&arg)
+            .into_iter()
+            .filter_map(Item::emoji)
+            .collect::<Vec<Item>>();
+
+        if parsed.is_empty() {
+            names.push(arg);
+        } else {
+            items.extend(parsed);
+        }
+    }
+
+    // stickers sent with the command, then everything in the message being replied to
+    items.extend(
+        context
+            .message
+            .sticker_items
+            .iter()
+            .filter_map(Item::sticker),
+    );
+
+    if items.is_empty() {
+        if let Some(reference) = &context.message.referenced_message {
+            items.extend(
+                emoji::parse(&reference.content)
+                    .into_iter()
+                    .filter_map(Item::emoji),
+            );
+            items.extend(reference.sticker_items.iter().filter_map(Item::sticker));
+        }
+    }
+
+    // names are given to the items in order, and there has to be one for each
+    if items.is_empty() || (!names.is_empty() && names.len() != items.len()) {
+        let reply = context.reply(USAGE).await?;
+        return Ok(Response::Message(reply));
+    }
+
+    if items.len() > MAX_ITEMS {
+        let reply = context
+            .reply(format!(
+                "You can only steal {} things at a time.",
+                MAX_ITEMS
+            ))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    for (item, name) in items.iter_mut().zip(names) {
+        item.name = name;
+    }
+
+    checks::has_permission(context, Permissions::MANAGE_EMOJIS_AND_STICKERS).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_EMOJIS_AND_STICKERS).await?;
+
+    // count the free slots, so we don't try uploads that are sure to fail
+    let mut slots = Slots::free(context, guild_id);
+
+    let mut stolen = Vec::new();
+    let mut failed = Vec::new();
+
+    for item in items.iter() {
+        let buffer = match fetch(context, item.uri.clone()).await {
+            Ok(buffer) => buffer,
+            Err(why) => {
+                failed.push(format!("`{}`: {}", item.name, why));
+                continue;
+            }
+        };
+
+        // links are animated if they lead to a gif, whatever they're called
+        let animated = match item.kind {
+            Kind::Emoji { animated } => animated,
+            Kind::Link => matches!(image::guess_format(&buffer), Ok(ImageFormat::Gif)),
+            Kind::Sticker { .. } => false,
+        };
+
+        let (free, kind) = match item.kind {
+            Kind::Sticker { .. } => (&mut slots.stickers, "sticker"),
+            _ if animated => (&mut slots.animated, "animated emoji"),
+            _ => (&mut slots.stills, "emoji"),
+        };
+
+        if *free == 0 {
+            failed.push(format!("`{}`: there are no free {} slots", item.name, kind));
+            continue;
+        }
+
+        match upload(context, item, buffer).await {
+            Ok(created) => {
+                *free -= 1;
+                stolen.push(created);
+            }
+            Err(why) => failed.push(format!("`{}`: {}", item.name, why)),
+        }
+    }
+
+    if failed.is_empty() {
+        context.react(&ResponseReaction::Success.value()).await?;
+        return Ok(Response::Reaction);
+    }
+
+    let mut content = String::new();
+
+    if !stolen.is_empty() {
+        writeln!(content, "Stole {}.", stolen.join(" "))?;
+    }
+
+    writeln!(content, "Couldn't steal:")?;
+    for failure in failed {
+        writeln!(content, "{}", failure)?;
+    }
+
+    let reply = context.reply(content).await?;
+
+    Ok(Response::Message(reply))
+}
@@ -0,0 +1,227 @@
+use crate::{
+    args::{Arg, DurationArg, RoleArg, UserArg},
+    checks,
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
+    table::TemporaryRole,
+};
+use chrono::{Duration, Utc};
+use sqlx::{Error as SqlxError, PgPool};
+use twilight_http::request::AuditLogReason;
+use twilight_model::{
+    guild::Permissions,
+    id::{GuildId, RoleId, UserId},
+};
+
+const MAX_DAYS: i64 = 366;
+
+const USAGE: &str = "USAGE: katze temprole [<member> <role> <duration> | list | remove]";
+const GIVE_USAGE: &str = "USAGE: katze temprole <member> <role> <duration>";
+const REMOVE_USAGE: &str = "USAGE: katze temprole remove <member> <role>";
+
+/// Whether a temporary role can last this long.
+pub fn valid(duration: Duration) -> bool {
+    duration >= Duration::minutes(1) && duration <= Duration::days(MAX_DAYS)
+}
+
+/// Take a role away from a member once the duration has passed, returning when. Scheduling the
+/// same role again moves the expiry.
+pub async fn schedule(
+    postgres: &PgPool,
+    guild_id: GuildId,
+    member_id: UserId,
+    role_id: RoleId,
+    duration: Duration,
+) -> Result<i64, SqlxError> {
+    let expires_at = (Utc::now() + duration).timestamp();
+
+    sqlx::query!(
+        "INSERT INTO temporary_roles (guild_id, member_id, role_id, expires_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, member_id, role_id) DO UPDATE SET
+        expires_at = EXCLUDED.expires_at;",
+        guild_id.to_string(),
+        member_id.to_string(),
+        role_id.to_string(),
+        expires_at,
+    )
+    .execute(postgres)
+    .await?;
+
+    Ok(expires_at)
+}
+
+/// Stop a role from expiring, returning whether it was going to.
+pub async fn cancel(
+    postgres: &PgPool,
+    guild_id: GuildId,
+    member_id: UserId,
+    role_id: RoleId,
+) -> Result<bool, SqlxError> {
+    let deleted = sqlx::query!(
+        "DELETE FROM temporary_roles WHERE
+        (guild_id = $1 AND member_id = $2 AND role_id = $3);",
+        guild_id.to_string(),
+        member_id.to_string(),
+        role_id.to_string(),
+    )
+    .execute(postgres)
+    .await?
+    .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+async fn give(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    // the duration comes last, so role names can have spaces in them
+    let (user, duration) = match (context.optional_arg::<UserArg>().await?, context.args.pop()) {
+        (Some(UserArg(user)), Some(duration)) => (user, duration),
+        _ => {
+            let reply = context.reply(GIVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let DurationArg(duration) = DurationArg::parse(context, &duration).await?;
+
+    let role = match context.optional_rest::<RoleArg>().await? {
+        Some(RoleArg(role)) => role,
+        None => {
+            let reply = context.reply(GIVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    if !valid(duration) {
+        let reply = context
+            .reply("Temporary roles can last from a minute to a year.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    checks::can_delegate(context, &role).await?;
+
+    context
+        .http()
+        .add_guild_member_role(guild_id, user.id, role.id)
+        .reason("Temporary role grant")?
+        .exec()
+        .await?;
+
+    let expires_at = schedule(context.postgres(), guild_id, user.id, role.id, duration).await?;
+
+    let reply = context
+        .reply(format!(
+            "Gave **{}** `{}` until <t:{2}:f> (<t:{2}:R>).",
+            user.name, role.name, expires_at
+        ))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn list(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let roles = sqlx::query_as!(
+        TemporaryRole,
+        "SELECT
+            guild_id AS \"guild_id: _\",
+            member_id AS \"member_id: _\",
+            role_id AS \"role_id: _\",
+            expires_at
+        FROM temporary_roles WHERE
+        (guild_id = $1)
+        ORDER BY expires_at;",
+        guild_id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    if roles.is_empty() {
+        let reply = context
+            .reply("There aren't any temporary roles in this server.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let lines = roles.into_iter().map(|temporary| {
+        let member = context
+            .cache()
+            .user(temporary.member_id.0)
+            .map(|user| user.name.clone())
+            .unwrap_or_else(|| temporary.member_id.to_string());
+
+        let role = context
+            .cache()
+            .role(temporary.role_id.0)
+            .map(|role| role.name.clone())
+            .unwrap_or_else(|| String::from("a deleted role"));
+
+        format!(
+            "**{}** `{}`, expires <t:{}:R>",
+            member, role, temporary.expires_at
+        )
+    });
+
+    let reply = context
+        .paginate(model::pages("Temporary roles:", lines))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    checks::has_permission(context, Permissions::MANAGE_ROLES).await?;
+    checks::bot_has_permission(context, Permissions::MANAGE_ROLES).await?;
+
+    let (user, role) = match (
+        context.optional_arg::<UserArg>().await?,
+        context.optional_rest::<RoleArg>().await?,
+    ) {
+        (Some(UserArg(user)), Some(RoleArg(role))) => (user, role),
+        _ => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::can_delegate(context, &role).await?;
+
+    if !cancel(context.postgres(), guild_id, user.id, role.id).await? {
+        let reply = context
+            .reply("That member doesn't have that role temporarily.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context
+        .http()
+        .remove_guild_member_role(guild_id, user.id, role.id)
+        .reason("Temporary role removal")?
+        .exec()
+        .await?;
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand_of(&["list", "ls", "remove", "rm"]) {
+        Some("list") | Some("ls") => list(context).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        _ if context.args.is_empty() => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+        // anything else is the member to give a role to
+        _ => give(context).await,
+    }
+}
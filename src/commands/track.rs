@@ -0,0 +1,445 @@
+use crate::{
+    args::DurationArg,
+    checks,
+    model::{self, GenericError, MessageContext, Response, ResponseReaction},
+    table::{MemberCount, PhraseInfraction, TrackedPhrase},
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use chrono_humanize::{Accuracy, HumanTime, Tense};
+use darkredis::ConnectionPool as RedisPool;
+use regex::{Regex, RegexBuilder};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Arc, RwLock},
+};
+use twilight_model::{
+    guild::Permissions,
+    id::{GuildId, UserId},
+};
+
+// the phrase migrated from vtrack, and where vtrack kept the last time it was said
+const LEGACY_KEY: &str = "katze:vore";
+const LEGACY_PATTERN: &str = r"\b[Vv][Oo][Rr][Ee]";
+
+const DEFAULT_COOLDOWN: i32 = 30;
+const DEFAULT_TEMPLATE: &str = "{user} said `{phrase}`, after {time} of silence.";
+const MAX_COOLDOWN: i32 = 10080;
+const MAX_PATTERN: usize = 200;
+const MAX_PHRASES: i64 = 25;
+const MAX_TEMPLATE: usize = 1500;
+// keeps user supplied patterns from compiling into something huge
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+const TOP_OFFENDERS: i64 = 10;
+
+const USAGE: &str = "USAGE: katze track [add | remove | list | stats]";
+const ADD_USAGE: &str = "USAGE: katze track add <phrase> [--regex] [--cooldown <duration>] \
+                         [--message <template>]";
+const REMOVE_USAGE: &str = "USAGE: katze track remove <id>";
+
+/// Build the matcher for a tracked phrase. Plain phrases match whole words, ignoring case.
+pub fn compile(pattern: &str, regex: bool) -> Result<Regex, regex::Error> {
+    let pattern = if regex {
+        pattern.to_string()
+    } else {
+        format!(r"(?i)\b{}\b", regex::escape(pattern))
+    };
+
+    RegexBuilder::new(&pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+/// A tracked phrase, ready to match messages against.
+#[derive(Debug)]
+pub struct Phrase {
+    pub id: i32,
+    pub pattern: String,
+    pub matcher: Regex,
+    pub cooldown: i32,
+    pub template: String,
+}
+
+/// Each guild's tracked phrases, compiled the first time a message in the guild needs them. A
+/// guild's entry is dropped whenever its phrases are added or removed.
+#[derive(Debug, Default)]
+pub struct PhraseCache(RwLock<HashMap<GuildId, Arc<Vec<Phrase>>>>);
+
+impl PhraseCache {
+    pub async fn get(
+        &self,
+        postgres: &PgPool,
+        guild_id: GuildId,
+    ) -> Result<Arc<Vec<Phrase>>, GenericError> {
+        if let Some(phrases) = self.0.read().unwrap().get(&guild_id) {
+            return Ok(phrases.clone());
+        }
+
+        let rows = sqlx::query!(
+            "SELECT id, pattern, regex, cooldown, template
+            FROM tracked_phrases WHERE (guild_id = $1)
+            ORDER BY id;",
+            guild_id.to_string(),
+        )
+        .fetch_all(postgres)
+        .await?;
+
+        // patterns were checked when they were added, but skip any that stopped compiling
+        let phrases = rows
+            .into_iter()
+            .filter_map(|row| {
+                let matcher = compile(&row.pattern, row.regex).ok()?;
+
+                Some(Phrase {
+                    id: row.id,
+                    pattern: row.pattern,
+                    matcher,
+                    cooldown: row.cooldown,
+                    template: row.template,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let phrases = Arc::new(phrases);
+        self.0.write().unwrap().insert(guild_id, phrases.clone());
+
+        Ok(phrases)
+    }
+
+    pub fn invalidate(&self, guild_id: GuildId) {
+        self.0.write().unwrap().remove(&guild_id);
+    }
+}
+
+/// Bring over the last time each server said the vtrack phrase, which vtrack kept in redis. The
+/// hash is deleted afterwards, so this only does anything on the first start after upgrading.
+pub async fn carry_over(postgres: &PgPool, redis: &RedisPool) -> Result<(), GenericError> {
+    let mut redis = redis.get().await;
+
+    for guild_id in redis.hkeys(LEGACY_KEY).await? {
+        let last_seen = match redis.hget(LEGACY_KEY, &guild_id).await? {
+            Some(last_seen) => String::from_utf8(last_seen)?.parse::<i64>()?,
+            None => continue,
+        };
+
+        sqlx::query!(
+            "UPDATE tracked_phrases SET last_seen = $2
+            WHERE (guild_id = $1 AND pattern = $3 AND regex AND last_seen IS NULL);",
+            String::from_utf8(guild_id)?,
+            last_seen,
+            LEGACY_PATTERN,
+        )
+        .execute(postgres)
+        .await?;
+    }
+
+    redis.del(LEGACY_KEY).await?;
+
+    Ok(())
+}
+
+pub async fn phrases(
+    context: &MessageContext,
+    guild_id: String,
+) -> Result<Vec<TrackedPhrase>, GenericError> {
+    let phrases = sqlx::query_as!(
+        TrackedPhrase,
+        "SELECT
+            id,
+            pattern,
+            regex,
+            cooldown,
+            template,
+            count,
+            last_seen
+        FROM tracked_phrases WHERE (guild_id = $1)
+        ORDER BY id;",
+        guild_id,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    Ok(phrases)
+}
+
+async fn add(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let regex = context.flag("--regex");
+
+    let cooldown = match context.option::<DurationArg>("--cooldown").await? {
+        Some(DurationArg(cooldown)) if cooldown.num_minutes() <= MAX_COOLDOWN as i64 => {
+            cooldown.num_minutes() as i32
+        }
+        Some(_) => {
+            let reply = context
+                .reply(format!(
+                    "`--cooldown` can be at most {} minutes.",
+                    MAX_COOLDOWN
+                ))
+                .await?;
+            return Ok(Response::Message(reply));
+        }
+        None => DEFAULT_COOLDOWN,
+    };
+
+    let template = match context.option::<String>("--message").await? {
+        Some(t) if t.is_empty() || t.chars().count() > MAX_TEMPLATE => {
+            let reply = context
+                .reply(format!(
+                    "`--message` must be from 1 to {} characters.",
+                    MAX_TEMPLATE
+                ))
+                .await?;
+            return Ok(Response::Message(reply));
+        }
+        Some(t) => t,
+        None => String::from(DEFAULT_TEMPLATE),
+    };
+
+    let pattern = context.optional_rest::<String>().await?.unwrap_or_default();
+
+    if pattern.is_empty() || pattern.chars().count() > MAX_PATTERN {
+        let reply = context.reply(ADD_USAGE).await?;
+        return Ok(Response::Message(reply));
+    }
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    if let Err(why) = compile(&pattern, regex) {
+        let reply = context
+            .reply(format!("That isn't a valid pattern:\n```{}```", why))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let count = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM tracked_phrases WHERE (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_one(context.postgres())
+    .await?
+    .count;
+
+    if count >= MAX_PHRASES {
+        let reply = context
+            .reply(format!(
+                "This server already tracks {} phrases, which is the most it can.",
+                MAX_PHRASES
+            ))
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    sqlx::query!(
+        "INSERT INTO tracked_phrases (guild_id, pattern, regex, cooldown, template)
+        VALUES ($1, $2, $3, $4, $5);",
+        guild_id.to_string(),
+        pattern,
+        regex,
+        cooldown,
+        template,
+    )
+    .execute(context.postgres())
+    .await?;
+
+    context.phrases().invalidate(guild_id);
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn remove(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let id = match context.optional_arg::<i32>().await? {
+        Some(id) => id,
+        None => {
+            let reply = context.reply(REMOVE_USAGE).await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    checks::has_permission(context, Permissions::MANAGE_GUILD).await?;
+
+    let removed = sqlx::query!(
+        "DELETE FROM tracked_phrases WHERE (guild_id = $1 AND id = $2);",
+        guild_id.to_string(),
+        id,
+    )
+    .execute(context.postgres())
+    .await?
+    .rows_affected();
+
+    if removed == 0 {
+        let reply = context
+            .reply("This server doesn't track a phrase with that id.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    context.phrases().invalidate(guild_id);
+
+    context.react(&ResponseReaction::Success.value()).await?;
+
+    Ok(Response::Reaction)
+}
+
+async fn list(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let phrases = phrases(context, guild_id.to_string()).await?;
+
+    if phrases.is_empty() {
+        let reply = context
+            .reply("This server doesn't track any phrases.")
+            .await?;
+        return Ok(Response::Message(reply));
+    }
+
+    let lines = phrases.into_iter().map(|phrase| {
+        format!(
+            "`{}` `{}`{}: said {} times, announced after {} minutes of silence\n> {}",
+            phrase.id,
+            phrase.pattern,
+            if phrase.regex { " (regex)" } else { "" },
+            phrase.count,
+            phrase.cooldown,
+            phrase.template.replace('\n', "\n> "),
+        )
+    });
+
+    let reply = context
+        .paginate(model::pages("Tracked phrases:", lines))
+        .await?;
+
+    Ok(Response::Message(reply))
+}
+
+fn name(context: &MessageContext, user_id: UserId) -> String {
+    context
+        .cache()
+        .user(user_id)
+        .map(|user| format!("{}#{:04}", user.name, user.discriminator))
+        .unwrap_or_else(|| user_id.to_string())
+}
+
+fn human(seconds: i64) -> String {
+    HumanTime::from(Duration::seconds(seconds)).to_text_en(Accuracy::Precise, Tense::Present)
+}
+
+async fn stats(context: &mut MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+
+    let phrases = phrases(context, guild_id.to_string()).await?;
+
+    // without an id, show the phrase that has been tracked the longest
+    let phrase = match context.optional_arg::<i32>().await? {
+        Some(id) => phrases.into_iter().find(|phrase| phrase.id == id),
+        None => phrases.into_iter().next(),
+    };
+
+    let phrase = match phrase {
+        Some(phrase) => phrase,
+        None => {
+            let reply = context
+                .reply("This server doesn't track a phrase with that id.")
+                .await?;
+            return Ok(Response::Message(reply));
+        }
+    };
+
+    let record = sqlx::query_as!(
+        PhraseInfraction,
+        "SELECT
+            member_id AS \"member_id: _\",
+            datetime,
+            gap
+        FROM phrase_infractions WHERE
+        (phrase_id = $1 AND gap IS NOT NULL)
+        ORDER BY gap DESC, datetime
+        LIMIT 1;",
+        phrase.id,
+    )
+    .fetch_optional(context.postgres())
+    .await?;
+
+    let offenders = sqlx::query_as!(
+        MemberCount,
+        "SELECT
+            member_id AS \"member_id: _\",
+            COUNT(*) AS \"count!\"
+        FROM phrase_infractions WHERE
+        (phrase_id = $1)
+        GROUP BY member_id
+        ORDER BY 2 DESC
+        LIMIT $2;",
+        phrase.id,
+        TOP_OFFENDERS,
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    let mut content = format!(
+        "**Stats for `{}`**\nSaid **{}** times.\n",
+        phrase.pattern, phrase.count
+    );
+
+    match phrase.last_seen {
+        Some(last_seen) => writeln!(
+            content,
+            "Current silence: **{}**.",
+            human(Utc::now().timestamp() - last_seen)
+        )?,
+        None => writeln!(content, "It hasn't been said yet.")?,
+    }
+
+    if let Some(PhraseInfraction {
+        member_id,
+        datetime,
+        gap: Some(gap),
+    }) = record
+    {
+        let date = NaiveDateTime::from_timestamp(datetime, 0).date();
+
+        writeln!(
+            content,
+            "Longest silence: **{}**, broken by {} on {}.",
+            human(gap),
+            name(context, member_id.0),
+            date
+        )?;
+    }
+
+    if !offenders.is_empty() {
+        content.push_str("\n**Top offenders**\n");
+
+        for row in offenders.iter() {
+            writeln!(
+                content,
+                "`{}` {}",
+                row.count,
+                name(context, row.member_id.0)
+            )?;
+        }
+    }
+
+    let reply = context.reply(content).await?;
+
+    Ok(Response::Message(reply))
+}
+
+pub async fn execute(context: &mut MessageContext) -> Result<Response, GenericError> {
+    match context.subcommand().as_deref() {
+        Some("add") => add(context).await,
+        Some("remove") | Some("rm") => remove(context).await,
+        Some("stats") => stats(context).await,
+        Some("list") | Some("ls") | None => list(context).await,
+        Some(_) => {
+            let reply = context.reply(USAGE).await?;
+            Ok(Response::Message(reply))
+        }
+    }
+}
@@ -1,49 +1,133 @@
 use crate::{
-    error::KatzeError,
-    model::{GenericError, MessageContext, Response, ResponseReaction},
-    table::Emoji,
+    args::UserArg,
+    cdn,
+    model::{self, GenericError, MessageContext, Response},
 };
-use chrono::{Duration, Utc};
-use http::uri::Uri;
-use hyper::{
-    body::{self, Body},
-    Request,
-};
-use lazy_static::lazy_static;
 use rand::seq::SliceRandom;
-use regex::Regex;
-use std::{collections::HashMap, convert::TryFrom, fmt::Write};
-use twilight_http::request::channel::reaction::RequestReactionType;
+use std::{fmt::Write, iter};
+use twilight_embed_builder::{EmbedBuilder, ImageSource};
 
 const HELP_TEXT: &str = include_str!("../../help.txt");
 
-lazy_static! {
-    static ref E: Regex =
-        Regex::new(r"<a?:(?P<name>[a-zA-Z1-9-_]{2,}):(?P<id>\d{17,21})>").unwrap();
+fn format_links(urls: impl Iterator<Item = (&'static str, String)>) -> String {
+    urls.map(|(format, url)| format!("[{}]({})", format, url))
+        .collect::<Vec<String>>()
+        .join(" | ")
 }
 
 pub async fn avatar(context: &mut MessageContext) -> Result<Response, GenericError> {
-    let found_user = context.find_member().await?;
-
-    let (id, avatar) = match found_user {
-        Some(user) => (user.id, user.avatar),
-        None => (
-            context.message.author.id,
-            context.message.author.avatar.clone(),
-        ),
+    let banner = context.flag("--banner");
+    let global = context.flag("--global");
+
+    let size = match context.option::<u16>("--size").await? {
+        Some(size) if cdn::SIZES.contains(&size) => size,
+        None => 2048,
+        Some(_) => {
+            let sizes = cdn::SIZES
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            let reply = context
+                .reply(format!("Size must be one of {}.", sizes))
+                .await?;
+            return Ok(Response::Message(reply));
+        }
     };
 
-    if let Some(avatar) = avatar {
-        let content = format!(
-            "https://cdn.discordapp.com/avatars/{}/{}?size=2048",
-            id, avatar
+    let user = match context.optional_rest::<UserArg>().await? {
+        Some(UserArg(user)) => user,
+        None => context.message.author.clone(),
+    };
+
+    let tag = format!("{}#{:04}", user.name, user.discriminator);
+
+    let embed = if banner {
+        // banners are only sent when the user is requested directly
+        let user = context.http().user(user.id).exec().await?.model().await?;
+
+        let hash = match user.banner.clone() {
+            Some(hash) => hash,
+            None => {
+                let reply = context
+                    .reply(format!("{} doesn't have a banner.", tag))
+                    .await?;
+                return Ok(Response::Message(reply));
+            }
+        };
+
+        let links = format_links(
+            cdn::formats(&hash)
+                .iter()
+                .map(|format| (*format, cdn::banner(user.id, &hash, format, size))),
         );
 
-        let reply = context.reply(content).await?;
-        return Ok(Response::Message(reply));
-    }
+        let url = cdn::banner(user.id, &hash, cdn::formats(&hash)[0], size);
+
+        EmbedBuilder::new()
+            .title(format!("Banner for {}", tag))
+            .description(links)
+            .color(user.accent_color.unwrap_or_default() as u32)
+            .image(ImageSource::url(url)?)
+            .build()?
+    } else {
+        // the cache doesn't hold member avatars, so ask discord for the member
+        let member_avatar = match (global, context.message.guild_id) {
+            (false, Some(guild_id)) => {
+                match context.http().guild_member(guild_id, user.id).exec().await {
+                    Ok(response) => response.model().await?.avatar.map(|hash| (guild_id, hash)),
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+
+        let global_url = user
+            .avatar
+            .as_ref()
+            .map(|hash| cdn::avatar(user.id, hash, cdn::formats(hash)[0], size))
+            .unwrap_or_else(|| cdn::default_avatar(user.discriminator));
+
+        let (title, url, links) = if let Some((guild_id, hash)) = member_avatar {
+            let links = format_links(cdn::formats(&hash).iter().map(|format| {
+                (
+                    *format,
+                    cdn::member_avatar(guild_id, user.id, &hash, format, size),
+                )
+            }));
+
+            (
+                format!("Server avatar for {}", tag),
+                cdn::member_avatar(guild_id, user.id, &hash, cdn::formats(&hash)[0], size),
+                format!("{}\n[global avatar]({})", links, global_url),
+            )
+        } else if let Some(hash) = &user.avatar {
+            let links = format_links(
+                cdn::formats(hash)
+                    .iter()
+                    .map(|format| (*format, cdn::avatar(user.id, hash, format, size))),
+            );
 
-    Ok(Response::None)
+            (format!("Avatar for {}", tag), global_url, links)
+        } else {
+            (
+                format!("Default avatar for {}", tag),
+                global_url.clone(),
+                format_links(iter::once(("png", global_url))),
+            )
+        };
+
+        EmbedBuilder::new()
+            .title(title)
+            .description(links)
+            .image(ImageSource::url(url)?)
+            .build()?
+    };
+
+    let reply = context.reply_embed(embed).await?;
+
+    Ok(Response::Message(reply))
 }
 
 pub async fn choose(context: &MessageContext) -> Result<Response, GenericError> {
@@ -59,66 +143,14 @@ pub async fn choose(context: &MessageContext) -> Result<Response, GenericError>
     Ok(Response::Message(reply))
 }
 
-pub async fn emojis(context: &MessageContext) -> Result<Response, GenericError> {
-    let one_week_ago = Utc::now()
-        .checked_sub_signed(Duration::days(7))
-        .unwrap()
-        .timestamp();
-
-    let emojis = sqlx::query_as!(
-        Emoji,
-        "SELECT
-            datetime,
-            guild_id AS \"guild_id: _\",
-            message_id AS \"message_id: _\",
-            member_id AS \"member_id: _\",
-            emoji_id AS \"emoji_id: _\",
-            reaction
-        FROM emojis WHERE
-        (datetime >= $1 AND guild_id = $2);",
-        one_week_ago,
-        context.message.guild_id.unwrap().to_string(),
-    )
-    .fetch_all(context.postgres())
-    .await?;
-
-    let mut counts = emojis
-        .iter()
-        .fold(HashMap::new(), |mut counts, emoji| {
-            {
-                let counter = counts.entry(emoji.emoji_id.to_string()).or_insert(0);
-                *counter += 1;
-            }
-
-            counts
-        })
-        .into_iter()
-        .collect::<Vec<(String, i32)>>();
-
-    counts.sort_by(|a, b| b.0.cmp(&a.0));
-    counts.sort_by(|a, b| b.1.cmp(&a.1));
-
-    let content = counts
-        .iter()
-        .enumerate()
-        .map(|(index, (id, count))| {
-            let mut formatted = format!("`{}` <:deleted:{}> ", count, id);
-            if (index + 1) % 10 == 0 {
-                formatted = format!("{}\n", formatted);
-            }
-
-            formatted
-        })
-        .collect::<Vec<String>>()
-        .join("");
-
-    let reply = context.reply(content).await?;
-
-    Ok(Response::Message(reply))
-}
-
 pub async fn help(context: &MessageContext) -> Result<Response, GenericError> {
-    let reply = context.reply(HELP_TEXT).await?;
+    // the help is longer than one message can be, so it's split between lines
+    let mut lines = HELP_TEXT.lines();
+    let header = lines.next().unwrap_or_default();
+
+    let reply = context
+        .paginate(model::pages(header, lines.map(String::from)))
+        .await?;
 
     Ok(Response::Message(reply))
 }
@@ -165,69 +197,3 @@ pub async fn shuffle(context: &mut MessageContext) -> Result<Response, GenericEr
 
     Ok(Response::Message(reply))
 }
-
-pub async fn steal(context: &mut MessageContext) -> Result<Response, GenericError> {
-    if let Some(emoji) = context.next() {
-        // create variables that hold the chain of information priority
-        let mut uri: Option<Uri> = Uri::try_from(&emoji).ok();
-        let mut name: Option<String> = None;
-
-        // set the uri and name from a custom emoji match
-        if uri.is_none() {
-            let caps = E
-                .captures(&emoji)
-                .ok_or_else(KatzeError::no_matching_emojis)?;
-
-            let formatted = format!(
-                "https://cdn.discordapp.com/emojis/{}.png?v=1",
-                caps.name("id")
-                    .ok_or_else(KatzeError::no_emoji_found)?
-                    .as_str()
-            );
-
-            uri = Uri::try_from(formatted).ok();
-            name = caps.name("name").map(|m| String::from(m.as_str()));
-        }
-
-        // override the name if there is another argument
-        if let Some(arg_name) = context.next() {
-            name = Some(arg_name);
-        }
-
-        // default the name to emoji if none
-        if name.is_none() {
-            name = Some("emoji".into());
-        }
-
-        // upload the emoji if everything checks out
-        if let (Some(uri), Some(name)) = (uri, name) {
-            let request = Request::get(uri).body(Body::empty())?;
-            let mut response = context.hyper().request(request).await?;
-            let buffer = body::to_bytes(response.body_mut()).await?;
-
-            let image = format!("data:image/png;base64,{}", base64::encode(buffer));
-
-            let emoji = context
-                .http()
-                .create_emoji(context.message.guild_id.unwrap(), &name, image.as_str())
-                .exec()
-                .await?
-                .model()
-                .await?;
-
-            context.react(&ResponseReaction::Success.value()).await?;
-            context
-                .react(&RequestReactionType::Custom {
-                    id: emoji.id,
-                    name: Some(name.as_str()),
-                })
-                .await?;
-
-            return Ok(Response::Reaction);
-        }
-    }
-
-    let reply = context.reply("USAGE: katze steal <emoji> [<name>]").await?;
-
-    Ok(Response::Message(reply))
-}
@@ -0,0 +1,274 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::{channel::ReactionType, id::EmojiId};
+
+lazy_static! {
+    // custom emojis are written as <:name:id>, or <a:name:id> when animated. names are 2 to 32
+    // ascii letters, digits, or underscores.
+    //
+    // unicode emojis are flags, keycaps, and pictographs with an optional skin tone or
+    // presentation selector, tag sequences for subdivision flags, and any number of those joined
+    // by zero width joiners
+    static ref EMOJI: Regex = Regex::new(
+        r"(?x)
+        <(?P<animated>a)?:(?P<name>[a-zA-Z0-9_]{2,32}):(?P<id>\d{15,21})>
+        | (?P<unicode>
+            \p{Regional_Indicator}{2}
+            | [0-9\#*]\x{FE0F}?\x{20E3}
+            | \p{Extended_Pictographic}(?:\p{Emoji_Modifier}|\x{FE0F})?(?:[\x{E0020}-\x{E007E}]+\x{E007F})?
+              (?:\x{200D}\p{Extended_Pictographic}(?:\p{Emoji_Modifier}|\x{FE0F})?)*
+        )"
+    )
+    .unwrap();
+    static ref NAME: Regex = Regex::new(r"^[a-zA-Z0-9_]{2,32}$").unwrap();
+    static ref PRESENTATION: Regex = Regex::new(r"^\p{Emoji_Presentation}$").unwrap();
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ParsedEmoji {
+    Custom {
+        animated: bool,
+        name: String,
+        id: EmojiId,
+    },
+    Unicode(String),
+}
+
+impl ParsedEmoji {
+    // the value stored in the emoji_id column
+    pub fn key(&self) -> String {
+        match self {
+            Self::Custom { id, .. } => id.to_string(),
+            Self::Unicode(emoji) => emoji.clone(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Custom { .. } => "custom",
+            Self::Unicode(_) => "unicode",
+        }
+    }
+
+    /// The emoji as a reaction the bot can add to a message.
+    pub fn request(&self) -> RequestReactionType<'_> {
+        match self {
+            Self::Custom { name, id, .. } => RequestReactionType::Custom {
+                id: *id,
+                name: Some(name),
+            },
+            Self::Unicode(emoji) => RequestReactionType::Unicode { name: emoji },
+        }
+    }
+}
+
+impl From<&ParsedEmoji> for ReactionType {
+    fn from(emoji: &ParsedEmoji) -> Self {
+        match emoji.clone() {
+            ParsedEmoji::Custom { animated, name, id } => Self::Custom {
+                animated,
+                id,
+                name: Some(name),
+            },
+            ParsedEmoji::Unicode(name) => Self::Unicode { name },
+        }
+    }
+}
+
+impl Display for ParsedEmoji {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Custom {
+                animated: true,
+                name,
+                id,
+            } => write!(f, "<a:{}:{}>", name, id),
+            Self::Custom { name, id, .. } => write!(f, "<:{}:{}>", name, id),
+            Self::Unicode(emoji) => f.write_str(emoji),
+        }
+    }
+}
+
+impl From<&ReactionType> for ParsedEmoji {
+    fn from(reaction: &ReactionType) -> Self {
+        match reaction {
+            ReactionType::Custom { animated, id, name } => Self::Custom {
+                animated: *animated,
+                name: name.clone().unwrap_or_default(),
+                id: *id,
+            },
+            ReactionType::Unicode { name } => Self::Unicode(name.clone()),
+        }
+    }
+}
+
+/// Whether a custom emoji can be given this name.
+pub fn valid_name(name: &str) -> bool {
+    NAME.is_match(name)
+}
+
+/// Every emoji in some text, in the order they appear.
+pub fn parse(content: &str) -> Vec<ParsedEmoji> {
+    EMOJI
+        .captures_iter(content)
+        .filter_map(|caps| {
+            if let Some(unicode) = caps.name("unicode") {
+                let unicode = unicode.as_str();
+
+                // a lone pictograph that defaults to text presentation, like a copyright sign,
+                // isn't counted unless it asks to be shown as an emoji
+                if unicode.chars().count() > 1 || PRESENTATION.is_match(unicode) {
+                    return Some(ParsedEmoji::Unicode(unicode.to_string()));
+                }
+
+                return None;
+            }
+
+            let id = caps["id"].parse().ok().and_then(EmojiId::new)?;
+
+            Some(ParsedEmoji::Custom {
+                animated: caps.name("animated").is_some(),
+                name: caps["name"].to_string(),
+                id,
+            })
+        })
+        .collect()
+}
+
+/// The emoji that makes up the whole of some text, ignoring surrounding whitespace.
+pub fn parse_one(content: &str) -> Option<ParsedEmoji> {
+    let content = content.trim();
+
+    match parse(content).as_slice() {
+        [emoji] if emoji.to_string() == content => Some(emoji.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(animated: bool, name: &str, id: u64) -> ParsedEmoji {
+        ParsedEmoji::Custom {
+            animated,
+            name: name.to_string(),
+            id: EmojiId::new(id).unwrap(),
+        }
+    }
+
+    fn unicode(emoji: &str) -> ParsedEmoji {
+        ParsedEmoji::Unicode(emoji.to_string())
+    }
+
+    #[test]
+    fn custom_emojis() {
+        assert_eq!(
+            parse("<:yeah:726252875696570368>"),
+            vec![custom(false, "yeah", 726252875696570368)]
+        );
+        assert_eq!(
+            parse("<a:catJAM:760208390375915530>"),
+            vec![custom(true, "catJAM", 760208390375915530)]
+        );
+    }
+
+    #[test]
+    fn custom_names() {
+        assert_eq!(
+            parse("<:cat0:123456789012345678>"),
+            vec![custom(false, "cat0", 123456789012345678)]
+        );
+        assert_eq!(
+            parse("<:0_0:123456789012345678>"),
+            vec![custom(false, "0_0", 123456789012345678)]
+        );
+        assert_eq!(
+            parse("<:pepe_hands_2020:123456789012345678>"),
+            vec![custom(false, "pepe_hands_2020", 123456789012345678)]
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert!(valid_name("cat0"));
+        assert!(valid_name("_a"));
+        assert!(!valid_name("a"));
+        assert!(!valid_name("no-dash"));
+        assert!(!valid_name(&"a".repeat(33)));
+    }
+
+    #[test]
+    fn invalid_custom() {
+        // too short, punctuation, not a snowflake, animated flag in the wrong place
+        assert!(parse("<:a:123456789012345678>").is_empty());
+        assert!(parse("<:no-dash:123456789012345678>").is_empty());
+        assert!(parse("<:no@at:123456789012345678>").is_empty());
+        assert!(parse("<:name:12345>").is_empty());
+        assert!(parse("<b:name:123456789012345678>").is_empty());
+        assert!(parse(":name:").is_empty());
+    }
+
+    #[test]
+    fn unicode_emojis() {
+        assert_eq!(parse("hi 😀"), vec![unicode("😀")]);
+        assert_eq!(parse("❤️"), vec![unicode("❤️")]);
+        assert_eq!(parse("1️⃣"), vec![unicode("1️⃣")]);
+        assert_eq!(parse("🇺🇸"), vec![unicode("🇺🇸")]);
+        assert_eq!(parse("🏴󠁧󠁢󠁥󠁮󠁧󠁿"), vec![unicode("🏴󠁧󠁢󠁥󠁮󠁧󠁿")]);
+    }
+
+    #[test]
+    fn unicode_sequences() {
+        assert_eq!(parse("👍🏿"), vec![unicode("👍🏿")]);
+        assert_eq!(parse("👩🏽‍🚀"), vec![unicode("👩🏽‍🚀")]);
+        assert_eq!(parse("🏳️‍🌈"), vec![unicode("🏳️‍🌈")]);
+        assert_eq!(parse("👨‍👩‍👧‍👦"), vec![unicode("👨‍👩‍👧‍👦")]);
+    }
+
+    #[test]
+    fn text_presentation() {
+        assert!(parse("© 2021 ™").is_empty());
+        assert!(parse("1 # *").is_empty());
+    }
+
+    #[test]
+    fn mixed() {
+        assert_eq!(
+            parse("lol <:cat0:123456789012345678>😂<a:cat0:123456789012345678> 😂"),
+            vec![
+                custom(false, "cat0", 123456789012345678),
+                unicode("😂"),
+                custom(true, "cat0", 123456789012345678),
+                unicode("😂"),
+            ]
+        );
+    }
+
+    #[test]
+    fn whole() {
+        assert_eq!(
+            parse_one(" <:cat0:123456789012345678> "),
+            Some(custom(false, "cat0", 123456789012345678))
+        );
+        assert_eq!(parse_one("😀"), Some(unicode("😀")));
+        assert_eq!(parse_one("😀😀"), None);
+        assert_eq!(parse_one("look 😀"), None);
+        assert_eq!(parse_one("cat0"), None);
+    }
+
+    #[test]
+    fn display() {
+        let samples = [
+            "<:cat0:123456789012345678>",
+            "<a:catJAM:760208390375915530>",
+            "👩🏽‍🚀",
+        ];
+
+        for sample in samples.iter() {
+            assert_eq!(parse_one(sample).unwrap().to_string(), *sample);
+        }
+    }
+}
@@ -8,22 +8,12 @@ use twilight_model::id::GuildId;
 #[derive(Debug)]
 pub enum KatzeError {
     GuildNotFound { id: GuildId },
-    NoEmojiFound,
-    NoMatchingEmojis,
 }
 
 impl KatzeError {
     pub fn guild_not_found(id: GuildId) -> GenericError {
         Box::new(KatzeError::GuildNotFound { id })
     }
-
-    pub fn no_emoji_found() -> GenericError {
-        Box::new(KatzeError::NoEmojiFound)
-    }
-
-    pub fn no_matching_emojis() -> GenericError {
-        Box::new(KatzeError::NoMatchingEmojis)
-    }
 }
 
 impl Display for KatzeError {
@@ -34,8 +24,6 @@ impl Display for KatzeError {
 
                 Display::fmt(id, f)
             }
-            Self::NoEmojiFound => f.write_str("no emoji id found"),
-            Self::NoMatchingEmojis => f.write_str("no matching emojis in supplied text"),
         }
     }
 }
@@ -0,0 +1,294 @@
+use crate::{
+    checks::{self, CheckError},
+    commands::rolemenu,
+    emoji::ParsedEmoji,
+    model::{BaseContext, GenericError},
+    table::{RoleMenu, RoleMenuEntry},
+};
+use log::error;
+use twilight_http::request::AuditLogReason;
+use twilight_model::{
+    application::{
+        callback::{CallbackData, InteractionResponse},
+        interaction::MessageComponentInteraction,
+    },
+    channel::{message::MessageFlags, Reaction},
+    id::{GuildId, RoleId, UserId},
+};
+
+fn role_name(context: &BaseContext, role_id: RoleId) -> String {
+    context
+        .cache()
+        .role(role_id)
+        .map(|role| format!("**{}**", role.name))
+        .unwrap_or_else(|| String::from("a deleted role"))
+}
+
+// give a member a role from a menu. in an exclusive menu, the other roles from the menu that the
+// member has are taken away, and returned.
+async fn give(
+    context: &BaseContext,
+    guild_id: GuildId,
+    user_id: UserId,
+    current: &[RoleId],
+    menu: &RoleMenu,
+    entries: &[RoleMenuEntry],
+    role_id: RoleId,
+) -> Result<Vec<RoleId>, GenericError> {
+    // the role may have changed since it was added to the menu
+    let role = context
+        .cache()
+        .role(role_id)
+        .map(|role| role.resource().clone());
+
+    if let Some(role) = role {
+        checks::bot_can_manage(context, guild_id, &role).await?;
+
+        if role.permissions.intersects(checks::DANGEROUS) {
+            let permissions = role.permissions & checks::DANGEROUS;
+
+            return Err(CheckError::DangerousRole(role.name, permissions).into());
+        }
+    }
+
+    context
+        .http()
+        .add_guild_member_role(guild_id, user_id, role_id)
+        .reason("Automated role menu grant")?
+        .exec()
+        .await?;
+
+    let mut taken = Vec::new();
+
+    if menu.exclusive {
+        for entry in entries.iter() {
+            let other = entry.role_id.0;
+
+            if other != role_id && current.contains(&other) {
+                take(context, guild_id, user_id, other).await?;
+                taken.push(other);
+            }
+        }
+    }
+
+    Ok(taken)
+}
+
+async fn take(
+    context: &BaseContext,
+    guild_id: GuildId,
+    user_id: UserId,
+    role_id: RoleId,
+) -> Result<(), GenericError> {
+    context
+        .http()
+        .remove_guild_member_role(guild_id, user_id, role_id)
+        .reason("Automated role menu removal")?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+// give or take away a role from a menu button, returning what happened
+async fn toggle(
+    context: &BaseContext,
+    guild_id: GuildId,
+    user_id: UserId,
+    current: &[RoleId],
+    menu_id: i32,
+    role_id: RoleId,
+) -> Result<String, GenericError> {
+    let menu = rolemenu::by_id(context.postgres(), guild_id, menu_id).await?;
+    let entries = match &menu {
+        Some(menu) => rolemenu::entries(context.postgres(), menu.id).await?,
+        None => Vec::new(),
+    };
+
+    let content = match menu {
+        Some(menu) if entries.iter().any(|entry| entry.role_id.0 == role_id) => {
+            if current.contains(&role_id) {
+                take(context, guild_id, user_id, role_id).await?;
+
+                format!("Took away {}.", role_name(context, role_id))
+            } else {
+                let taken = give(
+                    context, guild_id, user_id, current, &menu, &entries, role_id,
+                )
+                .await?;
+
+                let mut content = format!("Gave you {}.", role_name(context, role_id));
+
+                if !taken.is_empty() {
+                    let names = taken
+                        .into_iter()
+                        .map(|role_id| role_name(context, role_id))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+
+                    content.push_str(&format!(" Took away {}.", names));
+                }
+
+                content
+            }
+        }
+        _ => String::from("That role isn't in this menu anymore."),
+    };
+
+    Ok(content)
+}
+
+pub async fn interaction(
+    context: &BaseContext,
+    interaction: &MessageComponentInteraction,
+) -> Result<(), GenericError> {
+    // custom ids look like rolemenu:<menu id>:<role id>
+    let mut parts = interaction.data.custom_id.split(':');
+
+    let (menu_id, role_id) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(rolemenu::CUSTOM_ID), Some(menu_id), Some(role_id)) => {
+            match (
+                menu_id.parse::<i32>(),
+                role_id.parse().ok().and_then(RoleId::new),
+            ) {
+                (Ok(menu_id), Some(role_id)) => (menu_id, role_id),
+                _ => return Ok(()),
+            }
+        }
+        _ => return Ok(()),
+    };
+
+    let (guild_id, member) = match (interaction.guild_id, &interaction.member) {
+        (Some(guild_id), Some(member)) => (guild_id, member),
+        _ => return Ok(()),
+    };
+
+    let user_id = match &member.user {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+
+    let content = match toggle(context, guild_id, user_id, &member.roles, menu_id, role_id).await {
+        Ok(content) => content,
+        Err(why) => {
+            let content = match why.downcast_ref::<CheckError>() {
+                Some(check_error) => check_error.to_string(),
+                None => String::from("Something went wrong changing your roles. Try again later."),
+            };
+
+            error!("failed to use role menu {}\n{:?}", menu_id, why);
+            content
+        }
+    };
+
+    // only the member who clicked sees the response
+    let response = InteractionResponse::ChannelMessageWithSource(CallbackData {
+        allowed_mentions: None,
+        components: None,
+        content: Some(content),
+        embeds: Vec::new(),
+        flags: Some(MessageFlags::EPHEMERAL),
+        tts: None,
+    });
+
+    context
+        .http()
+        .interaction_callback(interaction.id, &interaction.token, &response)
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+// find the menu entry a reaction belongs to, if the reaction is on a reaction menu
+async fn reaction_entry(
+    context: &BaseContext,
+    reaction: &Reaction,
+) -> Result<Option<(RoleMenu, Vec<RoleMenuEntry>, RoleId)>, GenericError> {
+    let menu = match rolemenu::by_message(context.postgres(), reaction.message_id).await? {
+        Some(menu) if menu.reactions => menu,
+        _ => return Ok(None),
+    };
+
+    let entries = rolemenu::entries(context.postgres(), menu.id).await?;
+    let key = ParsedEmoji::from(&reaction.emoji).key();
+
+    let role_id = entries
+        .iter()
+        .find(|entry| entry.parsed().map(|emoji| emoji.key()).as_ref() == Some(&key))
+        .map(|entry| entry.role_id.0);
+
+    Ok(role_id.map(|role_id| (menu, entries, role_id)))
+}
+
+pub async fn reaction_add(context: &BaseContext, reaction: &Reaction) -> Result<(), GenericError> {
+    let (guild_id, member) = match (reaction.guild_id, &reaction.member) {
+        (Some(guild_id), Some(member)) if !member.user.bot => (guild_id, member),
+        _ => return Ok(()),
+    };
+
+    let (menu, entries, role_id) = match reaction_entry(context, reaction).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    let taken = give(
+        context,
+        guild_id,
+        reaction.user_id,
+        &member.roles,
+        &menu,
+        &entries,
+        role_id,
+    )
+    .await?;
+
+    // clear the reactions for roles that were taken away, so the menu matches the member's roles.
+    // this needs manage messages, and isn't worth failing over.
+    for entry in entries
+        .iter()
+        .filter(|entry| taken.contains(&entry.role_id.0))
+    {
+        if let Some(emoji) = entry.parsed() {
+            let _ = context
+                .http()
+                .delete_reaction(
+                    reaction.channel_id,
+                    reaction.message_id,
+                    &emoji.request(),
+                    reaction.user_id,
+                )
+                .exec()
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn reaction_remove(
+    context: &BaseContext,
+    reaction: &Reaction,
+) -> Result<(), GenericError> {
+    let guild_id = match reaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    // the bot's own reactions can be removed without it losing anything
+    let bot = context
+        .cache()
+        .user(reaction.user_id)
+        .map(|user| user.bot)
+        .unwrap_or(false);
+
+    if bot {
+        return Ok(());
+    }
+
+    if let Some((_, _, role_id)) = reaction_entry(context, reaction).await? {
+        take(context, guild_id, reaction.user_id, role_id).await?;
+    }
+
+    Ok(())
+}
@@ -1,107 +1,161 @@
 use crate::{
-    checks::CheckError,
-    commands, logger,
+    args::ArgError,
+    checks::{self, CheckError},
+    commands::{self, Command},
+    emoji, logger,
     model::{GenericError, MessageContext, Response},
-    table::Setting,
+    tracking::EmojiEvent,
 };
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use chrono_humanize::{Accuracy, HumanTime, Tense};
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::{collections::HashSet, fmt::Write};
 use twilight_mention::Mention;
 
-lazy_static! {
-    static ref E: Regex = Regex::new(r"<a?:[a-zA-Z1-9-_]{2,}:(?P<id>\d{17,21})>").unwrap();
-    static ref V: Regex = Regex::new(r"\b[Vv][Oo][Rr][Ee]").unwrap();
-}
-
-const THIRTY_MINUTES: i64 = 1800000;
-
 async fn emojis(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
     let now = Utc::now();
 
-    let ids = E
-        .captures_iter(context.message.content.as_ref())
-        .map(|c| c["id"].to_string())
-        .collect::<Vec<String>>();
+    let mut found = emoji::parse(context.message.content.as_ref());
 
-    // TODO: figure out pipelining here
-    for id in ids {
-        sqlx::query!(
-            "INSERT INTO emojis (datetime, guild_id, message_id, member_id, emoji_id)
-            VALUES ($1, $2, $3, $4, $5);",
-            now.timestamp(),
-            context.message.guild_id.unwrap().to_string(),
-            context.message.id.to_string(),
-            context.message.author.id.to_string(),
-            id.clone(),
-        )
-        .execute(context.postgres())
-        .await?;
+    if found.is_empty() {
+        return Ok(Response::None);
     }
 
+    // some servers would rather count each emoji once per message, no matter how many times it
+    // was repeated
+    let settings = context.emojis().settings(guild_id).await?;
+
+    if !settings.enabled {
+        return Ok(Response::None);
+    }
+
+    if settings.dedupe {
+        let mut seen = HashSet::new();
+        found.retain(|emoji| seen.insert(emoji.key()));
+    }
+
+    let events = found
+        .into_iter()
+        .map(|emoji| EmojiEvent {
+            datetime: now.timestamp(),
+            guild_id,
+            message_id: context.message.id,
+            member_id: context.message.author.id,
+            emoji,
+            reaction: false,
+        })
+        .collect();
+
+    context.emojis().push(events).await?;
+
     Ok(Response::None)
 }
 
-async fn vtrack(context: &MessageContext) -> Result<Response, GenericError> {
-    let guild_id = context.message.guild_id.unwrap().to_string();
+async fn tracked_phrases(context: &MessageContext) -> Result<Response, GenericError> {
+    let guild_id = context.message.guild_id.unwrap();
+    let content = context.message.content.as_str();
+
+    if content.is_empty() {
+        return Ok(Response::None);
+    }
+
+    let phrases = context.phrases().get(context.postgres(), guild_id).await?;
 
-    if V.is_match(context.message.content.as_ref()) {
-        let setting = Setting::query(
-            context.postgres().clone(),
-            context.message.guild_id.unwrap(),
+    let message_stamp = context.message.timestamp.iso_8601().to_string();
+    let message_stamp = DateTime::parse_from_rfc3339(message_stamp.as_ref())?;
+    let message_stamp = message_stamp.naive_utc();
+
+    let mut response = Response::None;
+
+    for phrase in phrases.iter() {
+        if !phrase.matcher.is_match(content) {
+            continue;
+        }
+
+        // count the phrase and set the latest time, getting back the time it was said before
+        let seen = sqlx::query!(
+            "UPDATE tracked_phrases AS phrase
+            SET count = phrase.count + 1, last_seen = $2
+            FROM tracked_phrases AS previous
+            WHERE (phrase.id = $1 AND previous.id = phrase.id)
+            RETURNING previous.last_seen, phrase.count;",
+            phrase.id,
+            message_stamp.timestamp(),
         )
+        .fetch_one(context.postgres())
         .await?;
 
-        if !setting.vtrack {
-            return Ok(Response::None);
-        }
+        let gap = seen
+            .last_seen
+            .map(|last_seen| message_stamp.timestamp() - last_seen);
 
-        // get the last time it was used, or 0 otherwise
-        let mut redis = context.redis().get().await;
-        let reply = redis.hget("katze:vore", &guild_id).await?;
-        let reply = reply.unwrap_or_else(|| b"0".to_owned().to_vec());
+        // the longest silence before this one, to tell if a record was broken
+        let record = sqlx::query!(
+            "SELECT MAX(gap) AS record FROM phrase_infractions WHERE (phrase_id = $1);",
+            phrase.id,
+        )
+        .fetch_one(context.postgres())
+        .await?
+        .record;
 
-        // determine the difference. use naive utc because we know both times are utc, and we only
-        // care about the resultant duration
-        let last_stamp = String::from_utf8(reply)?;
-        let last_stamp = last_stamp.parse::<i64>()?;
-        let last_stamp = Utc.timestamp(last_stamp, 0).naive_utc();
+        sqlx::query!(
+            "INSERT INTO phrase_infractions (phrase_id, guild_id, member_id, datetime, gap)
+            VALUES ($1, $2, $3, $4, $5);",
+            phrase.id,
+            guild_id.to_string(),
+            context.message.author.id.to_string(),
+            message_stamp.timestamp(),
+            gap,
+        )
+        .execute(context.postgres())
+        .await?;
 
-        let message_stamp = context.message.timestamp.iso_8601().to_string();
-        let message_stamp = DateTime::parse_from_rfc3339(message_stamp.as_ref())?;
-        let message_stamp = message_stamp.naive_utc();
+        // the first time a phrase is said, there's no silence to break
+        let last_stamp = match seen.last_seen {
+            Some(last_seen) => Utc.timestamp(last_seen, 0).naive_utc(),
+            None => continue,
+        };
 
+        // use naive utc because we know both times are utc, and we only care about the resultant
+        // duration
         let difference = message_stamp - last_stamp;
 
-        // set the latest time
-        redis
-            .hset(
-                "katze:vore",
-                &guild_id,
-                &message_stamp.timestamp().to_string(),
-            )
-            .await?;
-
-        // determine if we should send a message
-        if difference.num_milliseconds() < THIRTY_MINUTES {
-            return Ok(Response::None);
+        if difference.num_minutes() < phrase.cooldown as i64 {
+            continue;
         }
 
-        // send the message
-        let content = format!(
-            "{} has broken the silence and said the cursed word.
-This server has gone {} since the last infraction.",
-            context.message.author.mention(),
-            HumanTime::from(difference).to_text_en(Accuracy::Precise, Tense::Present),
-        );
+        let mut content = phrase
+            .template
+            .replace("{user}", &context.message.author.mention().to_string())
+            .replace("{phrase}", &phrase.pattern)
+            .replace("{count}", &seen.count.to_string())
+            .replace(
+                "{time}",
+                &HumanTime::from(difference).to_text_en(Accuracy::Precise, Tense::Present),
+            );
+
+        if let Some(record) = record.filter(|record| difference.num_seconds() > *record) {
+            write!(
+                content,
+                "\n🎉 That's a new record! The longest silence before this was {}.",
+                HumanTime::from(Duration::seconds(record))
+                    .to_text_en(Accuracy::Precise, Tense::Present),
+            )?;
+        }
 
         let reply = context.reply(content).await?;
+        response = Response::Message(reply);
+    }
 
-        return Ok(Response::Message(reply));
+    Ok(response)
+}
+
+async fn dispatch(context: &mut MessageContext, name: &str) -> Result<Response, GenericError> {
+    if !commands::perms::exempt(name) {
+        checks::command_rules(context, name).await?;
     }
 
-    Ok(Response::None)
+    commands::run(name, context).await
 }
 
 pub async fn handle(mut context: MessageContext) -> Result<(), GenericError> {
@@ -116,7 +170,7 @@ pub async fn handle(mut context: MessageContext) -> Result<(), GenericError> {
         #[allow(clippy::eval_order_dependence)]
         let autos = vec![
             ("emojis", emojis(&auto_context).await),
-            ("vtrack", vtrack(&auto_context).await),
+            ("phrases", tracked_phrases(&auto_context).await),
         ];
 
         for (name, result) in autos.into_iter() {
@@ -126,43 +180,26 @@ pub async fn handle(mut context: MessageContext) -> Result<(), GenericError> {
         }
     });
 
-    if let Some(prefix) = context.next() {
+    if let Some(prefix) = context.optional_arg::<String>().await? {
         if prefix != "katze" {
             return Ok(());
         }
     }
 
     // read the next word from the message as the command name
-    if let Some(command) = context.next() {
+    if let Some(command) = context.optional_arg::<String>().await? {
         // execute the command
-        let result = match command.as_ref() {
-            "add_image" | "pls" => commands::rotate::add_image(&context).await,
-            "avatar" => commands::util::avatar(&mut context).await,
-            "change-avatar" => commands::admin::change_avatar(&context).await,
-            "count" => commands::rotate::count(&context).await,
-            "choose" => commands::util::choose(&context).await,
-            "delete" | "remove" | "rm" => commands::rotate::delete(&mut context).await,
-            "emojis" => commands::util::emojis(&context).await,
-            "help" => commands::util::help(&context).await,
-            "invite" => commands::util::invite(&context).await,
-            "list" | "ls" => commands::rotate::list(&context).await,
-            "owo" => commands::fun::owo(&context).await,
-            "pick" => commands::rotate::pick(&mut context).await,
-            "ping" | "pong" => commands::util::ping(&context).await,
-            "roleme" => commands::roleme::execute(&mut context).await,
-            "rotate" | "rotato" | "tomato" | "potato" | "🍅" | "🥔" => {
-                commands::rotate::execute(&mut context).await
-            }
-            "show" => commands::rotate::show(&mut context).await,
-            "shuffle" => commands::util::shuffle(&mut context).await,
-            "steal" => commands::util::steal(&mut context).await,
-            _ => Ok(Response::None),
+        let result = match Command::find(command.as_ref()) {
+            Some(found) => dispatch(&mut context, found.name).await,
+            None => Ok(Response::None),
         };
 
-        // if we fail a check, tell the user
+        // if we fail a check, or the arguments are wrong, tell the user
         if let Err(why) = &result {
             if let Some(check_error) = why.downcast_ref::<CheckError>() {
                 context.reply(format!("{}", check_error)).await?;
+            } else if let Some(arg_error) = why.downcast_ref::<ArgError>() {
+                context.reply(format!("{}", arg_error)).await?;
             }
         }
 
@@ -1,9 +1,15 @@
+mod menu;
 mod message;
+pub mod persist;
 
-use crate::model::{BaseContext, GenericError, MessageContext};
+use crate::{
+    emoji::ParsedEmoji,
+    model::{BaseContext, GenericError, MessageContext},
+    tracking::EmojiEvent,
+};
 use chrono::Utc;
 use twilight_gateway::Event;
-use twilight_model::channel::ReactionType;
+use twilight_model::application::interaction::Interaction;
 
 pub async fn event(event: Event, context: BaseContext) -> Result<(), GenericError> {
     let now = Utc::now();
@@ -17,7 +23,7 @@ pub async fn event(event: Event, context: BaseContext) -> Result<(), GenericErro
             Ok(())
         }
         Event::MessageCreate(message) => {
-            message::handle(MessageContext::new(context, message)?).await
+            message::handle(MessageContext::new(context, message)).await
         }
         Event::GuildCreate(guild) => {
             log::info!("GUILD_CREATE {}:{}", guild.id, guild.name);
@@ -41,7 +47,31 @@ pub async fn event(event: Event, context: BaseContext) -> Result<(), GenericErro
 
             Ok(())
         }
+        Event::InteractionCreate(interaction) => {
+            if let Interaction::MessageComponent(component) = &interaction.0 {
+                menu::interaction(&context, component).await?;
+            }
+
+            Ok(())
+        }
+        Event::MemberAdd(member) => persist::restore(&context, &member).await,
+        Event::MessageDelete(message) => {
+            // a role menu can't be used once its message is gone
+            sqlx::query!(
+                "DELETE FROM role_menus WHERE (message_id = $1);",
+                message.id.to_string(),
+            )
+            .execute(context.postgres())
+            .await?;
+
+            Ok(())
+        }
         Event::ReactionAdd(reaction) => {
+            // a role menu that fails shouldn't stop the reaction from being counted
+            if let Err(why) = menu::reaction_add(&context, &reaction).await {
+                log::error!("failed to use role menu reaction\n{:?}", why);
+            }
+
             // store reaction in counts before we do anything
             // also ignore if the reaction comes from a bot
             if let Some(member) = &reaction.member {
@@ -49,42 +79,63 @@ pub async fn event(event: Event, context: BaseContext) -> Result<(), GenericErro
                     return Ok(());
                 }
 
-                if let ReactionType::Custom { id, .. } = &reaction.emoji {
-                    sqlx::query!(
-                        "INSERT INTO emojis
-                        (datetime, guild_id, message_id, member_id, emoji_id, reaction)
-                        VALUES ($1, $2, $3, $4, $5, true)",
-                        now.timestamp(),
-                        reaction.guild_id.unwrap().to_string(),
-                        reaction.message_id.to_string(),
-                        reaction.user_id.to_string(),
-                        id.to_string(),
-                    )
-                    .execute(context.postgres())
-                    .await?;
+                let guild_id = reaction.guild_id.unwrap();
+                if !context.emojis().settings(guild_id).await?.enabled {
+                    return Ok(());
                 }
+
+                let event = EmojiEvent {
+                    datetime: now.timestamp(),
+                    guild_id,
+                    message_id: reaction.message_id,
+                    member_id: reaction.user_id,
+                    emoji: ParsedEmoji::from(&reaction.emoji),
+                    reaction: true,
+                };
+
+                context.emojis().push(vec![event]).await?;
             }
 
             Ok(())
         }
         Event::ReactionRemove(reaction) => {
-            // this operation is safe, even if the user is a bot, because the delete operation will
-            // delete 0 rows.
-            if let ReactionType::Custom { id, .. } = &reaction.emoji {
-                sqlx::query!(
-                    "DELETE FROM emojis WHERE
-                    (message_id = $1 AND member_id = $2 AND emoji_id = $3 AND reaction = true);",
-                    reaction.message_id.to_string(),
-                    reaction.user_id.to_string(),
-                    id.to_string(),
-                )
-                .execute(context.postgres())
-                .await?;
+            if let Err(why) = menu::reaction_remove(&context, &reaction).await {
+                log::error!("failed to use role menu reaction\n{:?}", why);
             }
 
+            // this operation is safe, even if the user is a bot, because the delete operation will
+            // delete 0 rows. pending reactions are dropped from the buffer, and the rest are
+            // deleted from the database.
+            let key = ParsedEmoji::from(&reaction.emoji).key();
+
+            context
+                .emojis()
+                .discard(|event| {
+                    event.reaction
+                        && event.message_id == reaction.message_id
+                        && event.member_id == reaction.user_id
+                        && event.emoji.key() == key
+                })
+                .await;
+
+            sqlx::query!(
+                "DELETE FROM emojis WHERE
+                (message_id = $1 AND member_id = $2 AND emoji_id = $3 AND reaction = true);",
+                reaction.message_id.to_string(),
+                reaction.user_id.to_string(),
+                key,
+            )
+            .execute(context.postgres())
+            .await?;
+
             Ok(())
         }
         Event::ReactionRemoveAll(data) => {
+            context
+                .emojis()
+                .discard(|event| event.reaction && event.message_id == data.message_id)
+                .await;
+
             sqlx::query!(
                 "DELETE FROM emojis WHERE
                 (guild_id = $1 AND message_id = $2 AND reaction = true);",
@@ -97,17 +148,26 @@ pub async fn event(event: Event, context: BaseContext) -> Result<(), GenericErro
             Ok(())
         }
         Event::ReactionRemoveEmoji(data) => {
-            if let ReactionType::Custom { id, .. } = &data.emoji {
-                sqlx::query!(
-                    "DELETE FROM emojis WHERE
-                    (guild_id = $1 AND message_id = $2 AND emoji_id = $3 AND reaction = true);",
-                    data.guild_id.to_string(),
-                    data.message_id.to_string(),
-                    id.to_string(),
-                )
-                .execute(context.postgres())
-                .await?;
-            }
+            let key = ParsedEmoji::from(&data.emoji).key();
+
+            context
+                .emojis()
+                .discard(|event| {
+                    event.reaction
+                        && event.message_id == data.message_id
+                        && event.emoji.key() == key
+                })
+                .await;
+
+            sqlx::query!(
+                "DELETE FROM emojis WHERE
+                (guild_id = $1 AND message_id = $2 AND emoji_id = $3 AND reaction = true);",
+                data.guild_id.to_string(),
+                data.message_id.to_string(),
+                key,
+            )
+            .execute(context.postgres())
+            .await?;
 
             Ok(())
         }
@@ -0,0 +1,150 @@
+use crate::{
+    checks,
+    model::{BaseContext, GenericError},
+    table::{id::SqlxRoleId, Setting},
+};
+use twilight_gateway::Event;
+use twilight_http::request::AuditLogReason;
+use twilight_model::{
+    gateway::payload::incoming::MemberAdd,
+    id::{GuildId, RoleId, UserId},
+};
+
+/// A member leaving, with the roles they had.
+pub struct Departure {
+    guild_id: GuildId,
+    user_id: UserId,
+    roles: Vec<RoleId>,
+}
+
+/// The cache forgets members as they leave, so this has to be called before the cache sees the
+/// event.
+pub fn departure(context: &BaseContext, event: &Event) -> Option<Departure> {
+    match event {
+        Event::MemberRemove(removed) if !removed.user.bot => {
+            let member = match context.cache().member(removed.guild_id, removed.user.id) {
+                Some(member) => member,
+                None => {
+                    log::warn!(
+                        "{} left {} without being cached, so their roles weren't saved",
+                        removed.user.id,
+                        removed.guild_id
+                    );
+                    return None;
+                }
+            };
+
+            Some(Departure {
+                guild_id: removed.guild_id,
+                user_id: removed.user.id,
+                roles: member.roles().to_vec(),
+            })
+        }
+        _ => None,
+    }
+}
+
+// the roles that are kept for members who leave: every roleme role, and the server's extra ones
+async fn kept(context: &BaseContext, guild_id: GuildId) -> Result<Vec<RoleId>, GenericError> {
+    let kept = sqlx::query!(
+        "SELECT id AS \"id!: SqlxRoleId\" FROM roleme_roles WHERE (guild_id = $1)
+        UNION
+        SELECT role_id FROM persistent_roles WHERE (guild_id = $1);",
+        guild_id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?
+    .into_iter()
+    .map(|row| row.id.0)
+    .collect();
+
+    Ok(kept)
+}
+
+/// Remember the roles a member had when they left, if the server keeps them.
+pub async fn save(context: BaseContext, departure: Departure) -> Result<(), GenericError> {
+    let setting = Setting::query(context.postgres().clone(), departure.guild_id).await?;
+
+    if !setting.roleme_persist {
+        return Ok(());
+    }
+
+    let kept = kept(&context, departure.guild_id).await?;
+
+    // temporary roles aren't kept, or they could outlive their expiry
+    let temporary = sqlx::query!(
+        "SELECT role_id AS \"role_id: SqlxRoleId\" FROM temporary_roles WHERE
+        (guild_id = $1 AND member_id = $2);",
+        departure.guild_id.to_string(),
+        departure.user_id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?
+    .into_iter()
+    .map(|row| row.role_id.0)
+    .collect::<Vec<RoleId>>();
+
+    for role_id in departure
+        .roles
+        .into_iter()
+        .filter(|id| kept.contains(id) && !temporary.contains(id))
+    {
+        sqlx::query!(
+            "INSERT INTO departed_roles (guild_id, member_id, role_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING;",
+            departure.guild_id.to_string(),
+            departure.user_id.to_string(),
+            role_id.to_string(),
+        )
+        .execute(context.postgres())
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Give a member who rejoined the roles they had when they left.
+pub async fn restore(context: &BaseContext, member: &MemberAdd) -> Result<(), GenericError> {
+    let setting = Setting::query(context.postgres().clone(), member.guild_id).await?;
+
+    if !setting.roleme_persist || member.user.bot {
+        return Ok(());
+    }
+
+    let saved = sqlx::query!(
+        "DELETE FROM departed_roles WHERE
+        (guild_id = $1 AND member_id = $2)
+        RETURNING role_id AS \"role_id: SqlxRoleId\";",
+        member.guild_id.to_string(),
+        member.user.id.to_string(),
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    // the server may have changed its mind about a role while the member was gone
+    let kept = kept(context, member.guild_id).await?;
+
+    for row in saved {
+        let role_id = row.role_id.0;
+
+        let dangerous = match context.cache().role(role_id) {
+            Some(role) => role.permissions.intersects(checks::DANGEROUS),
+            None => continue,
+        };
+
+        if !kept.contains(&role_id) || dangerous {
+            continue;
+        }
+
+        // a role the bot can't manage anymore shouldn't stop the rest from being given back
+        let _ = context
+            .http()
+            .add_guild_member_role(member.guild_id, member.user.id, role_id)
+            .reason("Automated role restore after rejoining")?
+            .exec()
+            .await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use crate::model::{BaseContext, GenericError};
+use chrono::Utc;
+use log::info;
+
+// raw emoji usage older than each guild's retention window is rolled up into one row per emoji,
+// day, and kind of use, and then deleted. rollups lose the member, but keep everything else.
+pub async fn execute(context: BaseContext) -> Result<(), GenericError> {
+    let now = Utc::now();
+
+    // anything still in the buffer is recent, but write it out so nothing is left behind
+    context.emojis().flush().await?;
+
+    let result = sqlx::query!(
+        "WITH expired AS (
+            DELETE FROM emojis USING settings WHERE
+            (emojis.guild_id = settings.guild_id
+                AND emojis.datetime < ($1::BIGINT / 86400 - settings.emojis_retention) * 86400)
+            RETURNING
+                emojis.guild_id, emojis.datetime, emojis.emoji_id, emojis.emoji_type,
+                emojis.reaction
+        )
+        INSERT INTO emoji_days (guild_id, day, emoji_id, emoji_type, reaction, count)
+        SELECT guild_id, datetime / 86400, emoji_id, emoji_type, reaction, COUNT(*)
+        FROM expired
+        GROUP BY 1, 2, 3, 4, 5
+        ON CONFLICT (guild_id, day, emoji_id, emoji_type, reaction)
+        DO UPDATE SET count = emoji_days.count + EXCLUDED.count;",
+        now.timestamp(),
+    )
+    .execute(context.postgres())
+    .await?;
+
+    // the rows affected are the rollup rows written, not the raw rows deleted
+    info!(
+        "rolled up old emoji usage into {} daily rows",
+        result.rows_affected()
+    );
+
+    Ok(())
+}
@@ -1,9 +1,14 @@
+mod emojis;
 mod rotate;
+mod temproles;
 
 use crate::model::{BaseContext, GenericError};
 use chrono::{Duration, Timelike, Utc};
-use log::info;
-use tokio::time::{self, Instant as TokioInstant};
+use log::{error, info};
+use std::time::Duration as StdDuration;
+use tokio::time::{self, Instant as TokioInstant, MissedTickBehavior};
+
+const TEMPROLES_EVERY: StdDuration = StdDuration::from_secs(60);
 
 fn next_hour() -> TokioInstant {
     let instant = TokioInstant::now();
@@ -20,9 +25,27 @@ fn next_hour() -> TokioInstant {
     instant + difference.to_std().unwrap()
 }
 
+// temporary roles should expire on time, so they're checked every minute instead of every hour.
+// the first check is right away, to catch up on anything that expired while the bot was down.
+// each check finishes before the next starts, so a slow one can't take the same role away twice.
+async fn every_minute(context: BaseContext) {
+    let mut interval = time::interval(TEMPROLES_EVERY);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(why) = temproles::execute(context.clone()).await {
+            error!("failed to check temporary roles\n{:?}", why);
+        }
+    }
+}
+
 pub async fn start(context: BaseContext) -> Result<(), GenericError> {
     info!("starting jobs loop");
 
+    tokio::spawn(every_minute(context.clone()));
+
     loop {
         // wait until the next hour
         time::sleep_until(next_hour()).await;
@@ -31,5 +54,10 @@ pub async fn start(context: BaseContext) -> Result<(), GenericError> {
         let now = Utc::now();
         info!("{} running jobs", now.timestamp());
         tokio::spawn(rotate::execute(context.clone()));
+
+        // roll up old emoji usage once a day
+        if now.hour() == 0 {
+            tokio::spawn(emojis::execute(context.clone()));
+        }
     }
 }
@@ -0,0 +1,78 @@
+use crate::{
+    model::{BaseContext, GenericError},
+    table::TemporaryRole,
+};
+use chrono::Utc;
+use log::{error, info, warn};
+use twilight_http::{error::ErrorType as HttpErrorType, request::AuditLogReason};
+
+// take away temporary roles that have expired. a role that's gone, because the member left or the
+// role was deleted, is forgotten. so is a role the bot isn't allowed to take away anymore, since
+// retrying won't help. anything else, like being rate limited, is tried again next time.
+pub async fn execute(context: BaseContext) -> Result<(), GenericError> {
+    let now = Utc::now();
+
+    let expired = sqlx::query_as!(
+        TemporaryRole,
+        "SELECT
+            guild_id AS \"guild_id: _\",
+            member_id AS \"member_id: _\",
+            role_id AS \"role_id: _\",
+            expires_at
+        FROM temporary_roles WHERE
+        (expires_at <= $1);",
+        now.timestamp(),
+    )
+    .fetch_all(context.postgres())
+    .await?;
+
+    let mut removed = 0;
+
+    for temporary in expired.iter() {
+        let result = context
+            .http()
+            .remove_guild_member_role(
+                temporary.guild_id.0,
+                temporary.member_id.0,
+                temporary.role_id.0,
+            )
+            .reason("Temporary role expired")?
+            .exec()
+            .await;
+
+        if let Err(why) = result {
+            match why.kind() {
+                HttpErrorType::Response { status, .. } if status.raw() == 404 => {}
+                HttpErrorType::Response { status, .. } if status.raw() == 403 => {
+                    warn!(
+                        "not allowed to take away temporary role {} in {}, so it was left",
+                        temporary.role_id.0, temporary.guild_id.0
+                    );
+                }
+                _ => {
+                    error!("failed to take away temporary role\n{:?}", why);
+                    continue;
+                }
+            }
+        }
+
+        sqlx::query!(
+            "DELETE FROM temporary_roles WHERE
+            (guild_id = $1 AND member_id = $2 AND role_id = $3 AND expires_at = $4);",
+            temporary.guild_id.to_string(),
+            temporary.member_id.to_string(),
+            temporary.role_id.to_string(),
+            temporary.expires_at,
+        )
+        .execute(context.postgres())
+        .await?;
+
+        removed += 1;
+    }
+
+    if removed > 0 {
+        info!("took away {} temporary roles", removed);
+    }
+
+    Ok(())
+}
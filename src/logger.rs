@@ -1,4 +1,5 @@
 use crate::{
+    args::ArgError,
     checks::CheckError,
     model::{GenericError, MessageContext, Response},
 };
@@ -44,14 +45,13 @@ pub fn error(context: &MessageContext, why: GenericError, command: String) {
                 general.message,
             );
         }
-    } else if let Some(shellwords::MismatchedQuotes) =
-        why.downcast_ref::<shellwords::MismatchedQuotes>()
-    {
-        error!(
-            "channel:{} timestamp:{} command:{} mismatched quotes",
+    } else if let Some(why) = why.downcast_ref::<ArgError>() {
+        info!(
+            "channel:{} timestamp:{} command:{} bad arguments: {}",
             context.message.channel_id,
             context.message.timestamp.iso_8601(),
-            command
+            command,
+            why
         );
     } else if let Some(why) = why.downcast_ref::<CheckError>() {
         info!(
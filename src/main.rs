@@ -1,23 +1,35 @@
+mod args;
+mod cdn;
+mod chart;
 mod checks;
 mod commands;
+mod emoji;
 mod error;
 mod handler;
 mod jobs;
 mod logger;
 mod model;
+mod search;
+mod staff;
 mod table;
+mod tracking;
 
-use crate::model::{BaseContext, GenericError};
+use crate::{
+    model::{BaseContext, GenericError},
+    staff::Staff,
+    tracking::EmojiWriter,
+};
 use darkredis::ConnectionPool as RedisPool;
 use futures_util::stream::StreamExt;
 use hyper::Client as HyperClient;
 use hyper_rustls::HttpsConnector;
 use sqlx::PgPool;
 use std::sync::Arc;
+use tokio::signal;
 use twilight_cache_inmemory::InMemoryCache;
-use twilight_gateway::Cluster;
+use twilight_gateway::{Cluster, Event};
 use twilight_http::Client as HttpClient;
-use twilight_model::gateway::Intents;
+use twilight_model::gateway::{payload::outgoing::RequestGuildMembers, Intents};
 use twilight_standby::Standby;
 
 #[deny(clippy::all)]
@@ -49,6 +61,9 @@ async fn main() -> Result<(), GenericError> {
     // connect to redis
     let redis = RedisPool::create((&dotenv::var("REDIS")?).into(), None, 4).await?;
 
+    // vtrack kept its timestamps in redis, so they couldn't move with the rest of it in a migration
+    commands::track::carry_over(&postgres, &redis).await?;
+
     // build the hyper client
     let https = HttpsConnector::with_native_roots();
     let hyper = HyperClient::builder().build(https);
@@ -56,6 +71,7 @@ async fn main() -> Result<(), GenericError> {
     // create the primary parental context, with new instances of all members
     let context = BaseContext::new(
         InMemoryCache::new(),
+        EmojiWriter::new(postgres.clone()),
         HttpClient::new(dotenv::var("TOKEN")?),
         hyper,
         postgres,
@@ -63,22 +79,60 @@ async fn main() -> Result<(), GenericError> {
         Standby::new(),
     );
 
+    // find out who runs the bot once, instead of on every command
+    context.set_staff(Staff::load(context.http()).await?);
+
     // start the cluster in the background
-    let cluster_spawn = Arc::new(cluster);
+    let cluster = Arc::new(cluster);
+    let cluster_spawn = cluster.clone();
     tokio::spawn(async move {
         cluster_spawn.up().await;
     });
 
     // start jobs
     tokio::spawn(jobs::start(context.clone()));
+    tokio::spawn(tracking::start(context.clone()));
+
+    // listen for events, until we are asked to stop
+    loop {
+        let event = tokio::select! {
+            event = events.next() => event,
+            _ = signal::ctrl_c() => None,
+        };
+
+        let (shard_id, event) = match event {
+            Some(event) => event,
+            None => break,
+        };
+
+        // discord only sends the members it has to, so ask for the rest when a guild becomes
+        // available. otherwise most members who leave aren't cached, and their roles are lost.
+        if let Event::GuildCreate(guild) = &event {
+            let cluster = cluster.clone();
+            let request = RequestGuildMembers::builder(guild.id).query("", None);
+
+            tokio::spawn(async move {
+                if let Err(why) = cluster.command(shard_id, &request).await {
+                    log::error!("failed to request guild members\n{:?}", why);
+                }
+            });
+        }
+
+        // members' roles have to be saved before the cache forgets them
+        if let Some(departure) = handler::persist::departure(&context, &event) {
+            tokio::spawn(handler::persist::save(context.clone(), departure));
+        }
 
-    // listen for events
-    while let Some((_, event)) = events.next().await {
         context.cache().update(&event);
         context.standby().process(&event);
 
         tokio::spawn(handler::event(event, context.clone()));
     }
 
+    // write out anything still buffered before exiting
+    log::info!("shutting down");
+    cluster.down();
+    context.emojis().flush().await?;
+
     Ok(())
 }
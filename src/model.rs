@@ -1,19 +1,79 @@
+use crate::{
+    args::{self, ArgError},
+    commands::track::PhraseCache,
+    logger,
+    search::{self, Match},
+    staff::Staff,
+    tracking::EmojiWriter,
+};
 use darkredis::ConnectionPool as RedisPool;
+use futures_util::stream::StreamExt;
 use hyper::client::{Client as HyperClient, HttpConnector};
 use hyper_rustls::HttpsConnector;
+use lazy_static::lazy_static;
+use regex::Regex;
 use sqlx::PgPool;
-use std::{error::Error, sync::Arc};
+use std::{
+    cmp,
+    error::Error,
+    fmt::Write,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::time;
 use twilight_cache_inmemory::InMemoryCache;
-use twilight_http::{request::channel::reaction::RequestReactionType, Client as HttpClient};
+use twilight_http::{
+    error::ErrorType as HttpErrorType, request::channel::reaction::RequestReactionType,
+    Client as HttpClient,
+};
 use twilight_model::{
-    channel::{message::Mention, Message, ReactionType},
+    channel::{embed::Embed, Message, ReactionType},
     gateway::payload::incoming::{MessageCreate, ReactionAdd},
-    id::EmojiId,
+    guild::Role,
+    id::{EmojiId, RoleId, UserId},
+    user::User,
 };
 use twilight_standby::Standby;
 
+lazy_static! {
+    static ref USER_ID: Regex = Regex::new(r"^(?:<@!?)?(?P<id>\d{15,21})>?$").unwrap();
+    static ref ROLE_ID: Regex = Regex::new(r"^(?:<@&)?(?P<id>\d{15,21})>?$").unwrap();
+}
+
+const DISCORD_EPOCH: u64 = 1420070400000;
+const DISAMBIGUATE_LIMIT: usize = 10;
+const DISAMBIGUATE_TIMEOUT: Duration = Duration::from_secs(30);
+const PAGE_BACK: &str = "⬅️";
+const PAGE_FORWARD: &str = "➡️";
+const PAGE_LIMIT: usize = 1900;
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
 pub type GenericError = Box<dyn Error + Send + Sync>;
 
+// the first 42 bits of a snowflake are milliseconds since the discord epoch
+pub fn created_at(id: u64) -> u64 {
+    ((id >> 22) + DISCORD_EPOCH) / 1000
+}
+
+// split lines into pages that fit in a message, with a header repeated on every page
+pub fn pages(header: &str, lines: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::from(header);
+
+    for line in lines {
+        if page.len() + line.len() + 1 > PAGE_LIMIT && page.len() > header.len() {
+            pages.push(page);
+            page = String::from(header);
+        }
+
+        page.push('\n');
+        page.push_str(&line);
+    }
+
+    pages.push(page);
+    pages
+}
+
 pub enum ResponseReaction {
     Success,
     Failure,
@@ -54,6 +114,7 @@ pub struct BaseContext(Arc<BaseContextRef>);
 impl BaseContext {
     pub fn new(
         cache: InMemoryCache,
+        emojis: EmojiWriter,
         http: HttpClient,
         hyper: HyperClient<HttpsConnector<HttpConnector>>,
         postgres: PgPool,
@@ -62,11 +123,15 @@ impl BaseContext {
     ) -> Self {
         Self(Arc::new(BaseContextRef {
             cache,
+            emojis,
             http,
             hyper,
+            phrases: PhraseCache::default(),
             postgres,
             redis,
+            staff: RwLock::new(Arc::new(Staff::default())),
             standby,
+            started: Instant::now(),
         }))
     }
 
@@ -74,6 +139,10 @@ impl BaseContext {
         &self.0.cache
     }
 
+    pub fn emojis(&self) -> &EmojiWriter {
+        &self.0.emojis
+    }
+
     pub fn http(&self) -> &HttpClient {
         &self.0.http
     }
@@ -82,6 +151,10 @@ impl BaseContext {
         &self.0.hyper
     }
 
+    pub fn phrases(&self) -> &PhraseCache {
+        &self.0.phrases
+    }
+
     pub fn postgres(&self) -> &PgPool {
         &self.0.postgres
     }
@@ -90,19 +163,35 @@ impl BaseContext {
         &self.0.redis
     }
 
+    pub fn staff(&self) -> Arc<Staff> {
+        self.0.staff.read().unwrap().clone()
+    }
+
+    pub fn set_staff(&self, staff: Staff) {
+        *self.0.staff.write().unwrap() = Arc::new(staff);
+    }
+
     pub fn standby(&self) -> &Standby {
         &self.0.standby
     }
+
+    pub fn uptime(&self) -> Duration {
+        self.0.started.elapsed()
+    }
 }
 
 #[derive(Debug)]
 pub struct BaseContextRef {
     pub cache: InMemoryCache,
+    pub emojis: EmojiWriter,
     pub http: HttpClient,
     pub hyper: HyperClient<HttpsConnector<HttpConnector>>,
+    pub phrases: PhraseCache,
     pub postgres: PgPool,
     pub redis: RedisPool,
+    pub staff: RwLock<Arc<Staff>>,
     pub standby: Standby,
+    pub started: Instant,
 }
 
 #[derive(Clone, Debug)]
@@ -113,14 +202,18 @@ pub struct MessageContext {
 }
 
 impl MessageContext {
-    pub fn new(base: BaseContext, message: Box<MessageCreate>) -> Result<Self, GenericError> {
-        let args = shellwords::split(&message.content)?;
+    pub fn new(base: BaseContext, message: Box<MessageCreate>) -> Self {
+        let args = args::tokenize(&message.content);
 
-        Ok(Self {
+        Self {
             base,
             message,
             args,
-        })
+        }
+    }
+
+    pub fn base(&self) -> &BaseContext {
+        &self.base
     }
 
     pub fn cache(&self) -> &InMemoryCache {
@@ -180,28 +273,311 @@ impl MessageContext {
         })
     }
 
-    pub async fn find_member(&self) -> Result<Option<Mention>, GenericError> {
-        if !self.message.mentions.is_empty() {
-            return Ok(Some(self.message.mentions[0].clone()));
+    pub async fn disambiguate(&self, options: &[String]) -> Result<Option<usize>, GenericError> {
+        let count = cmp::min(options.len(), DISAMBIGUATE_LIMIT);
+
+        let mut content =
+            String::from("Found more than one match. Reply with a number to pick one:\n");
+        for (index, option) in options.iter().take(count).enumerate() {
+            writeln!(content, "`{}` {}", index + 1, option)?;
+        }
+
+        if options.len() > count {
+            writeln!(
+                content,
+                "...and {} more. If yours isn't listed, try again with more of its name.",
+                options.len() - count
+            )?;
+        }
+
+        let bystander = self.reply(content).await?;
+
+        // wait for the user to pick a number from the list
+        let author_id = self.message.author.id;
+        let choice = self
+            .standby()
+            .wait_for_message(self.message.channel_id, move |event: &MessageCreate| {
+                event.author.id == author_id
+                    && matches!(event.content.trim().parse::<usize>(), Ok(n) if n >= 1 && n <= count)
+            });
+
+        let choice = time::timeout(DISAMBIGUATE_TIMEOUT, choice).await;
+
+        // clear out the message and return the result
+        self.http()
+            .delete_message(bystander.channel_id, bystander.id)
+            .exec()
+            .await?;
+
+        match choice {
+            Ok(message) => Ok(Some(message?.content.trim().parse::<usize>()? - 1)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn emojis(&self) -> &EmojiWriter {
+        self.base.emojis()
+    }
+
+    pub async fn find_member(&self, query: &str) -> Result<Option<User>, GenericError> {
+        let query = query.trim();
+
+        if query.is_empty() {
+            return Ok(None);
+        }
+
+        // mentions and raw ids can be looked up directly
+        // an id too big to be one is searched for like a name
+        let id = USER_ID
+            .captures(query)
+            .and_then(|caps| caps["id"].parse().ok())
+            .and_then(UserId::new);
+
+        if let Some(id) = id {
+            if let Some(user) = self.cache().user(id) {
+                return Ok(Some(user.value().clone()));
+            }
+
+            return match self.http().user(id).exec().await {
+                Ok(response) => Ok(Some(response.model().await?)),
+                Err(why) => match why.kind() {
+                    HttpErrorType::Response { status, .. } if status.raw() == 404 => Ok(None),
+                    _ => Err(why.into()),
+                },
+            };
+        }
+
+        // everything else is searched for in the cached members of the guild
+        let guild_id = match self.message.guild_id {
+            Some(guild_id) => guild_id,
+            None => return Ok(None),
+        };
+
+        let member_ids = self
+            .cache()
+            .guild_members(guild_id)
+            .map(|members| members.iter().copied().collect::<Vec<UserId>>())
+            .unwrap_or_default();
+
+        let members = member_ids
+            .into_iter()
+            .filter_map(|id| {
+                let user = self.cache().user(id)?.value().clone();
+                let nick = self
+                    .cache()
+                    .member(guild_id, id)
+                    .and_then(|member| member.nick().map(String::from));
+
+                Some((user, nick))
+            })
+            .collect::<Vec<(User, Option<String>)>>();
+
+        // username#discriminator has to match exactly
+        if let Some((name, discriminator)) = query.rsplit_once('#') {
+            if let (4, Ok(discriminator)) = (discriminator.len(), discriminator.parse::<u16>()) {
+                let found = members.into_iter().find(|(user, _)| {
+                    user.discriminator == discriminator
+                        && user.name.to_lowercase() == name.to_lowercase()
+                });
+
+                return Ok(found.map(|(user, _)| user));
+            }
+        }
+
+        // otherwise match against usernames and nicknames, closest matches first
+        let mut found = search::best(
+            query,
+            members.into_iter().map(|(user, nick)| {
+                let mut names = vec![user.name.clone()];
+                names.extend(nick.clone());
+
+                ((user, nick), names)
+            }),
+        );
+
+        if found.len() > 1 {
+            let options = found
+                .iter()
+                .map(|(user, nick)| match nick {
+                    Some(nick) => format!("{}#{:04} ({})", user.name, user.discriminator, nick),
+                    None => format!("{}#{:04}", user.name, user.discriminator),
+                })
+                .collect::<Vec<String>>();
+
+            return Ok(self
+                .disambiguate(&options)
+                .await?
+                .map(|index| found.swap_remove(index).0));
+        }
+
+        Ok(found.pop().map(|(user, _)| user))
+    }
+
+    /// The roles the author of the message has.
+    pub fn author_roles(&self) -> Vec<RoleId> {
+        match (&self.message.member, self.message.guild_id) {
+            (Some(member), _) => member.roles.clone(),
+            (None, Some(guild_id)) => self
+                .cache()
+                .member(guild_id, self.message.author.id)
+                .map(|member| member.roles().to_vec())
+                .unwrap_or_default(),
+            (None, None) => Vec::new(),
         }
+    }
+
+    /// Every cached role in the guild the message was sent in.
+    pub fn guild_roles(&self) -> Vec<Role> {
+        self.message
+            .guild_id
+            .and_then(|guild_id| self.cache().guild_roles(guild_id))
+            .map(|roles| {
+                roles
+                    .iter()
+                    .filter_map(|id| self.cache().role(*id).map(|role| role.resource().clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-        // TODO: wait for CachedGuild.members
-        //
-        // let guild_id = context.message.guild_id.ok_or(FindMemberError::NoGuild)?;
-        // let guild = context.base.0.cache.guild(guild_id).await?;
+    /// Find a role among the candidates by mention, id, or name, ignoring case. Names that only
+    /// match partly are confirmed with the author first.
+    pub async fn find_role(
+        &self,
+        query: &str,
+        candidates: &[Role],
+    ) -> Result<Option<Role>, GenericError> {
+        let query = query.trim();
 
-        // let found = members
-        //     .iter()
-        //     .find(|&member| member.display_name().into_owned() == search_str.to_string());
+        if let Some(caps) = ROLE_ID.captures(query) {
+            let id = caps["id"].parse().ok().and_then(RoleId::new);
 
-        // if found.is_some() {
-        //     let user = found.unwrap().user.read();
-        //     return Some(user.clone());
-        // } else {
-        //     return Some(msg.author.clone());
-        // }
+            return Ok(candidates.iter().find(|role| Some(role.id) == id).cloned());
+        }
+
+        let (matched, mut found) = match search::ranked(
+            query,
+            candidates
+                .iter()
+                .map(|role| (role, vec![role.name.clone()])),
+        ) {
+            Some(ranked) => ranked,
+            None => return Ok(None),
+        };
+
+        // picking from several matches is already a confirmation
+        if found.len() > 1 {
+            let options = found
+                .iter()
+                .map(|role| format!("`{}`", role.name))
+                .collect::<Vec<String>>();
+
+            return Ok(self
+                .disambiguate(&options)
+                .await?
+                .map(|index| found.swap_remove(index).clone()));
+        }
+
+        let role = match found.pop() {
+            Some(role) => role,
+            None => return Ok(None),
+        };
+
+        if matched == Match::Exact
+            || self
+                .confirm(format!("Did you mean `{}`?", role.name))
+                .await?
+        {
+            Ok(Some(role.clone()))
+        } else {
+            Ok(None)
+        }
+    }
 
-        Ok(None)
+    /// Find every role in a comma separated list, like `red, blue`, with `find_role`. A role with
+    /// a comma in its name can still be given by itself.
+    pub async fn find_roles(
+        &self,
+        query: &str,
+        candidates: &[Role],
+    ) -> Result<Vec<Role>, GenericError> {
+        let whole = candidates
+            .iter()
+            .find(|role| role.name.to_lowercase() == query.trim().to_lowercase());
+
+        if let Some(role) = whole {
+            return Ok(vec![role.clone()]);
+        }
+
+        let mut roles: Vec<Role> = Vec::new();
+
+        for part in query
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            match self.find_role(part, candidates).await? {
+                Some(role) if roles.iter().any(|r| r.id == role.id) => {}
+                Some(role) => roles.push(role),
+                None => {
+                    return Err(ArgError::invalid(format!(
+                        "Couldn't find the role `{}`.",
+                        part
+                    )))
+                }
+            }
+        }
+
+        if roles.is_empty() {
+            return Err(ArgError::invalid("Missing a role."));
+        }
+
+        Ok(roles)
+    }
+
+    async fn flip_pages(&self, message: Message, pages: Vec<String>) -> Result<(), GenericError> {
+        let author_id = self.message.author.id;
+        let mut reactions = self
+            .standby()
+            .wait_for_reaction_stream(message.id, move |event: &ReactionAdd| {
+                event.user_id == author_id
+            });
+
+        let mut index: usize = 0;
+        while let Ok(Some(reaction)) = time::timeout(PAGE_TIMEOUT, reactions.next()).await {
+            let name = match &reaction.emoji {
+                ReactionType::Unicode { name } => name.as_str(),
+                _ => continue,
+            };
+
+            index = match name {
+                PAGE_BACK => index.checked_sub(1).unwrap_or(pages.len() - 1),
+                PAGE_FORWARD => (index + 1) % pages.len(),
+                _ => continue,
+            };
+
+            let content = format!("{}\nPage {}/{}", pages[index], index + 1, pages.len());
+
+            self.http()
+                .update_message(message.channel_id, message.id)
+                .content(Some(&content))?
+                .exec()
+                .await?;
+
+            // removing the reaction lets it be pressed again, but needs manage messages
+            let _ = self
+                .http()
+                .delete_reaction(
+                    message.channel_id,
+                    message.id,
+                    &RequestReactionType::Unicode { name },
+                    author_id,
+                )
+                .exec()
+                .await;
+        }
+
+        Ok(())
     }
 
     pub fn http(&self) -> &HttpClient {
@@ -212,6 +588,43 @@ impl MessageContext {
         self.base.hyper()
     }
 
+    pub async fn paginate(&self, pages: Vec<String>) -> Result<Message, GenericError> {
+        if pages.len() < 2 {
+            return self
+                .reply(pages.into_iter().next().unwrap_or_default())
+                .await;
+        }
+
+        let content = format!("{}\nPage 1/{}", pages[0], pages.len());
+        let message = self.reply(content).await?;
+
+        for name in [PAGE_BACK, PAGE_FORWARD] {
+            self.http()
+                .create_reaction(
+                    message.channel_id,
+                    message.id,
+                    &RequestReactionType::Unicode { name },
+                )
+                .exec()
+                .await?;
+        }
+
+        // flip through pages in the background so the command can finish
+        let context = self.clone();
+        let bystander = message.clone();
+        tokio::spawn(async move {
+            if let Err(why) = context.flip_pages(bystander, pages).await {
+                logger::error(&context, why, "paginate".to_string());
+            }
+        });
+
+        Ok(message)
+    }
+
+    pub fn phrases(&self) -> &PhraseCache {
+        self.base.phrases()
+    }
+
     pub fn postgres(&self) -> &PgPool {
         self.base.postgres()
     }
@@ -229,12 +642,15 @@ impl MessageContext {
         self.base.redis()
     }
 
+    pub fn set_staff(&self, staff: Staff) {
+        self.base.set_staff(staff)
+    }
+
     pub async fn reply(&self, content: impl Into<String>) -> Result<Message, GenericError> {
         let message = self
             .http()
             .create_message(self.message.channel_id)
-            .content(&content.into())
-            .unwrap()
+            .content(&content.into())?
             .exec()
             .await?
             .model()
@@ -243,22 +659,28 @@ impl MessageContext {
         Ok(message)
     }
 
+    pub async fn reply_embed(&self, embed: Embed) -> Result<Message, GenericError> {
+        let message = self
+            .http()
+            .create_message(self.message.channel_id)
+            .embeds(&[embed])?
+            .exec()
+            .await?
+            .model()
+            .await?;
+
+        Ok(message)
+    }
+
+    pub fn staff(&self) -> Arc<Staff> {
+        self.base.staff()
+    }
+
     pub fn standby(&self) -> &Standby {
         self.base.standby()
     }
-}
 
-impl Iterator for MessageContext {
-    type Item = String;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if !self.args.is_empty() {
-            let mut args = self.args.clone().into_iter();
-            let arg = args.next();
-            self.args = args.collect::<Vec<Self::Item>>();
-            arg
-        } else {
-            None
-        }
+    pub fn uptime(&self) -> Duration {
+        self.base.uptime()
     }
 }
@@ -0,0 +1,168 @@
+use std::cmp;
+
+/// How closely a candidate matched a query. Lower is better.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Match {
+    Exact,
+    Prefix,
+    Fuzzy(usize),
+}
+
+/// Levenshtein edit distance between two strings, counted in chars.
+pub fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + cmp::min(previous, cmp::min(row[j], row[j + 1]))
+            };
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Compare a query against a candidate, ignoring case. Fuzzy matches are only accepted if the
+/// edit distance is within a third of the query length.
+pub fn score(query: &str, candidate: &str) -> Option<Match> {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+
+    if query.is_empty() {
+        return None;
+    }
+
+    if candidate == query {
+        return Some(Match::Exact);
+    }
+
+    if candidate.starts_with(&query) {
+        return Some(Match::Prefix);
+    }
+
+    let limit = cmp::max(1, query.chars().count() / 3);
+    let distance = distance(&query, &candidate);
+
+    if distance <= limit {
+        Some(Match::Fuzzy(distance))
+    } else {
+        None
+    }
+}
+
+/// Find every item that shares the best match against the query. Each item may be known by
+/// several names, and the best of them is used.
+pub fn best<T>(query: &str, items: impl IntoIterator<Item = (T, Vec<String>)>) -> Vec<T> {
+    ranked(query, items)
+        .map(|(_, found)| found)
+        .unwrap_or_default()
+}
+
+/// Like `best`, but also returns how well the items matched.
+pub fn ranked<T>(
+    query: &str,
+    items: impl IntoIterator<Item = (T, Vec<String>)>,
+) -> Option<(Match, Vec<T>)> {
+    let mut best: Option<Match> = None;
+    let mut found = Vec::new();
+
+    for (item, names) in items {
+        let matched = names.iter().filter_map(|name| score(query, name)).min();
+
+        if let Some(matched) = matched {
+            match best {
+                Some(current) if matched > current => continue,
+                Some(current) if matched == current => found.push(item),
+                _ => {
+                    best = Some(matched);
+                    found = vec![item];
+                }
+            }
+        }
+    }
+
+    best.map(|best| (best, found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(names: &[&str]) -> Vec<(usize, Vec<String>)> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (index, vec![name.to_string()]))
+            .collect()
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("cat", ""), 3);
+        assert_eq!(distance("", "cat"), 3);
+        assert_eq!(distance("cat", "cat"), 0);
+        assert_eq!(distance("cat", "cut"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("ümlaut", "umlaut"), 1);
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(score("Mods", "mods"), Some(Match::Exact));
+        assert_eq!(score("mod", "Moderators"), Some(Match::Prefix));
+        assert_eq!(score("moderatrs", "moderators"), Some(Match::Fuzzy(1)));
+        assert_eq!(score("cat", "cut"), Some(Match::Fuzzy(1)));
+        assert_eq!(score("cat", "dog"), None);
+        assert_eq!(score("", "anything"), None);
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(Match::Exact < Match::Prefix);
+        assert!(Match::Prefix < Match::Fuzzy(0));
+        assert!(Match::Fuzzy(1) < Match::Fuzzy(2));
+    }
+
+    #[test]
+    fn best_match_wins() {
+        let names = items(&["moderators", "mod", "mob"]);
+        assert_eq!(ranked("mod", names), Some((Match::Exact, vec![1])));
+
+        let names = items(&["mob", "moderators"]);
+        assert_eq!(ranked("mod", names), Some((Match::Prefix, vec![1])));
+
+        let names = items(&["artists", "artist"]);
+        assert_eq!(best("artst", names), vec![1]);
+
+        assert_eq!(ranked("mod", items(&["admin", "helper"])), None);
+        assert!(best("mod", items(&["admin"])).is_empty());
+    }
+
+    #[test]
+    fn ties() {
+        let names = items(&["red", "Red", "blue"]);
+        assert_eq!(ranked("red", names), Some((Match::Exact, vec![0, 1])));
+
+        let names = items(&["green", "greenish", "grey"]);
+        assert_eq!(ranked("gre", names), Some((Match::Prefix, vec![0, 1, 2])));
+    }
+
+    #[test]
+    fn aliases() {
+        let names = vec![
+            (0, vec![String::from("moderators"), String::from("mods")]),
+            (1, vec![String::from("modern")]),
+        ];
+        assert_eq!(ranked("mods", names), Some((Match::Exact, vec![0])));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::model::GenericError;
+use std::collections::{HashMap, HashSet};
+use twilight_http::Client as HttpClient;
+use twilight_model::{
+    id::UserId,
+    oauth::team::{Team, TeamMembershipState},
+};
+
+/// The people who run the bot. Owners can do anything, and staff can see how it's doing.
+#[derive(Debug, Default)]
+pub struct Staff {
+    owners: HashSet<UserId>,
+    staff: HashSet<UserId>,
+}
+
+// a comma separated list of user ids from the config file, or the environment if the file doesn't
+// have it. it may not be set at all.
+fn ids(config: &HashMap<String, String>, key: &str) -> Result<HashSet<UserId>, GenericError> {
+    let value = match config.get(key).cloned().or_else(|| dotenv::var(key).ok()) {
+        Some(value) => value,
+        None => return Ok(HashSet::new()),
+    };
+
+    let mut ids = HashSet::new();
+
+    for id in value.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        let id = id
+            .parse::<u64>()
+            .ok()
+            .and_then(UserId::new)
+            .ok_or_else(|| format!("{} has an invalid user id: {}", key, id))?;
+
+        ids.insert(id);
+    }
+
+    Ok(ids)
+}
+
+// the team's owner runs the bot, and the members who accepted their invites help
+fn team(team: Team) -> (UserId, impl Iterator<Item = UserId>) {
+    let members = team
+        .members
+        .into_iter()
+        .filter(|member| member.membership_state == TeamMembershipState::Accepted)
+        .map(|member| member.user.id);
+
+    (team.owner_user_id, members)
+}
+
+impl Staff {
+    /// Read the owners and staff from the config, along with whoever owns the application on
+    /// discord. `OWNER` is still read, for configs from before there could be more than one.
+    pub async fn load(http: &HttpClient) -> Result<Self, GenericError> {
+        Self::load_from(http, &HashMap::new()).await
+    }
+
+    /// Read the owners and staff again, from the config file as it is now. Anything the file
+    /// doesn't set keeps the value it had at startup. The file is read directly, because changing
+    /// the environment while other threads might read it isn't safe.
+    pub async fn reload(http: &HttpClient) -> Result<Self, GenericError> {
+        let mut config = HashMap::new();
+
+        // the replacement, dotenv::from_path, only sets variables in the environment
+        #[allow(deprecated)]
+        for item in dotenv::dotenv_iter()? {
+            let (key, value) = item?;
+            config.insert(key, value);
+        }
+
+        Self::load_from(http, &config).await
+    }
+
+    async fn load_from(
+        http: &HttpClient,
+        config: &HashMap<String, String>,
+    ) -> Result<Self, GenericError> {
+        let mut owners = ids(config, "OWNERS")?;
+        owners.extend(ids(config, "OWNER")?);
+
+        let mut staff = ids(config, "STAFF")?;
+
+        let application = http
+            .current_user_application()
+            .exec()
+            .await?
+            .model()
+            .await?;
+
+        match application.team {
+            Some(application_team) => {
+                let (owner, members) = team(application_team);
+
+                owners.insert(owner);
+                staff.extend(members);
+            }
+            None => {
+                owners.insert(application.owner.id);
+            }
+        }
+
+        Ok(Self { owners, staff })
+    }
+
+    pub fn is_owner(&self, user_id: UserId) -> bool {
+        self.owners.contains(&user_id)
+    }
+
+    /// Owners are staff too.
+    pub fn is_staff(&self, user_id: UserId) -> bool {
+        self.is_owner(user_id) || self.staff.contains(&user_id)
+    }
+
+    pub fn owners(&self) -> usize {
+        self.owners.len()
+    }
+
+    /// How many staff there are who aren't owners.
+    pub fn staff(&self) -> usize {
+        self.staff.difference(&self.owners).count()
+    }
+}
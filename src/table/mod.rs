@@ -22,7 +22,10 @@ pub struct Setting {
     pub movies_role: Option<SqlxRoleId>,
     pub rotate_every: i32,
     pub rotate_enabled: bool,
-    pub vtrack: bool,
+    pub emojis_dedupe: bool,
+    pub emojis_enabled: bool,
+    pub emojis_retention: i32,
+    pub roleme_persist: bool,
 }
 
 impl Setting {
@@ -41,7 +44,10 @@ impl Setting {
                     movies_role AS \"movies_role: _\",
                     rotate_every,
                     rotate_enabled,
-                    vtrack
+                    emojis_dedupe,
+                    emojis_enabled,
+                    emojis_retention,
+                    roleme_persist
                 FROM settings WHERE (guild_id = $1);",
                 guild_id.to_string(),
             )
@@ -63,6 +69,73 @@ pub struct RolemeRole {
     pub guild_id: SqlxGuildId,
     pub id: SqlxRoleId,
     pub color: Option<String>,
+    pub group_id: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct RolemeGroup {
+    pub id: i32,
+    pub name: String,
+    pub exclusive: bool,
+    pub max_picks: Option<i32>,
+    pub required_role_id: Option<SqlxRoleId>,
+}
+
+#[derive(Debug)]
+pub struct TemporaryRole {
+    pub guild_id: SqlxGuildId,
+    pub member_id: SqlxUserId,
+    pub role_id: SqlxRoleId,
+    pub expires_at: i64,
+}
+
+#[derive(Debug)]
+pub struct CommandRule {
+    pub id: i32,
+    pub command: String,
+    pub kind: String,
+    pub target_id: String,
+    pub allow: bool,
+}
+
+#[derive(Debug)]
+pub struct PaletteColor {
+    pub color: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TrackedPhrase {
+    pub id: i32,
+    pub pattern: String,
+    pub regex: bool,
+    pub cooldown: i32,
+    pub template: String,
+    pub count: i64,
+    pub last_seen: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct PhraseInfraction {
+    pub member_id: SqlxUserId,
+    pub datetime: i64,
+    pub gap: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct RoleMenu {
+    pub id: i32,
+    pub channel_id: SqlxChannelId,
+    pub message_id: SqlxMessageId,
+    pub title: String,
+    pub exclusive: bool,
+    pub reactions: bool,
+}
+
+#[derive(Debug)]
+pub struct RoleMenuEntry {
+    pub role_id: SqlxRoleId,
+    pub emoji: Option<String>,
 }
 
 #[derive(Debug)]
@@ -93,3 +166,40 @@ pub struct Emoji {
     pub emoji_id: SqlxEmojiId,
     pub reaction: bool,
 }
+
+#[derive(Debug)]
+pub struct EmojiCount {
+    pub emoji_id: SqlxEmojiId,
+    pub count: i64,
+}
+
+#[derive(Debug)]
+pub struct EmojiDayCount {
+    pub emoji_id: SqlxEmojiId,
+    pub day: i64,
+    pub count: i64,
+}
+
+#[derive(Debug)]
+pub struct EmojiUsage {
+    pub messages: i64,
+    pub reactions: i64,
+}
+
+#[derive(Debug)]
+pub struct UnicodeEmojiCount {
+    pub emoji: String,
+    pub count: i64,
+}
+
+#[derive(Debug)]
+pub struct MemberCount {
+    pub member_id: SqlxUserId,
+    pub count: i64,
+}
+
+#[derive(Debug)]
+pub struct DayCount {
+    pub day: i64,
+    pub count: i64,
+}
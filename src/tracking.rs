@@ -0,0 +1,183 @@
+use crate::{
+    emoji::ParsedEmoji,
+    model::{BaseContext, GenericError},
+    table::Setting,
+};
+use log::{error, info};
+use sqlx::PgPool;
+use std::{collections::HashMap, mem, sync::Mutex as StdMutex, time::Duration};
+use tokio::{sync::Mutex, time};
+use twilight_model::id::{GuildId, MessageId, UserId};
+
+const BATCH_SIZE: usize = 500;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+// how many events are kept while postgres can't be written to, before the oldest are dropped
+const MAX_BUFFERED: usize = 50_000;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EmojiEvent {
+    pub datetime: i64,
+    pub guild_id: GuildId,
+    pub message_id: MessageId,
+    pub member_id: UserId,
+    pub emoji: ParsedEmoji,
+    pub reaction: bool,
+}
+
+/// Whether a guild records emoji usage, and whether repeats in a message count once.
+#[derive(Clone, Copy, Debug)]
+pub struct EmojiSettings {
+    pub enabled: bool,
+    pub dedupe: bool,
+}
+
+// emoji usage is buffered in memory and written in batches, instead of one insert per emoji.
+// anything that deletes from the emojis table has to flush or discard first, so that it doesn't
+// miss rows that are still waiting in the buffer.
+#[derive(Debug)]
+pub struct EmojiWriter {
+    postgres: PgPool,
+    buffer: StdMutex<Vec<EmojiEvent>>,
+    // looked up once per guild, instead of for every message and reaction
+    settings: StdMutex<HashMap<GuildId, EmojiSettings>>,
+    // held for the duration of a write, so that flushes happen one at a time and in order
+    writing: Mutex<()>,
+}
+
+impl EmojiWriter {
+    pub fn new(postgres: PgPool) -> Self {
+        Self {
+            postgres,
+            buffer: StdMutex::new(Vec::new()),
+            settings: StdMutex::new(HashMap::new()),
+            writing: Mutex::new(()),
+        }
+    }
+
+    pub async fn push(&self, events: Vec<EmojiEvent>) -> Result<(), GenericError> {
+        let full = {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.extend(events);
+            buffer.len() >= BATCH_SIZE
+        };
+
+        if full {
+            self.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&self) -> Result<(), GenericError> {
+        let _writing = self.writing.lock().await;
+
+        let events = mem::take(&mut *self.buffer.lock().unwrap());
+
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut datetimes = Vec::with_capacity(events.len());
+        let mut guild_ids = Vec::with_capacity(events.len());
+        let mut message_ids = Vec::with_capacity(events.len());
+        let mut member_ids = Vec::with_capacity(events.len());
+        let mut emoji_ids = Vec::with_capacity(events.len());
+        let mut emoji_types = Vec::with_capacity(events.len());
+        let mut reactions = Vec::with_capacity(events.len());
+
+        for event in events.iter() {
+            datetimes.push(event.datetime);
+            guild_ids.push(event.guild_id.to_string());
+            message_ids.push(event.message_id.to_string());
+            member_ids.push(event.member_id.to_string());
+            emoji_ids.push(event.emoji.key());
+            emoji_types.push(event.emoji.kind().to_string());
+            reactions.push(event.reaction);
+        }
+
+        let result = sqlx::query!(
+            "INSERT INTO emojis
+            (datetime, guild_id, message_id, member_id, emoji_id, emoji_type, reaction)
+            SELECT * FROM UNNEST(
+                $1::BIGINT[], $2::VARCHAR[], $3::VARCHAR[],
+                $4::VARCHAR[], $5::VARCHAR[], $6::VARCHAR[], $7::BOOLEAN[]
+            );",
+            &datetimes,
+            &guild_ids,
+            &message_ids,
+            &member_ids,
+            &emoji_ids,
+            &emoji_types,
+            &reactions,
+        )
+        .execute(&self.postgres)
+        .await;
+
+        // put the events back, ahead of anything newer, so they're written next time instead of
+        // being lost. if postgres stays down, the oldest are dropped so memory doesn't run out.
+        if let Err(why) = result {
+            let mut buffer = self.buffer.lock().unwrap();
+            let newer = mem::replace(&mut *buffer, events);
+            buffer.extend(newer);
+
+            if buffer.len() > MAX_BUFFERED {
+                let dropped = buffer.len() - MAX_BUFFERED;
+                buffer.drain(..dropped);
+
+                error!(
+                    "dropped {} buffered emoji events that couldn't be written",
+                    dropped
+                );
+            }
+
+            return Err(why.into());
+        }
+
+        Ok(())
+    }
+
+    /// The guild's emoji settings. These are cached, so anything that changes them has to call
+    /// `forget_settings` afterwards.
+    pub async fn settings(&self, guild_id: GuildId) -> Result<EmojiSettings, GenericError> {
+        if let Some(settings) = self.settings.lock().unwrap().get(&guild_id) {
+            return Ok(*settings);
+        }
+
+        let setting = Setting::query(self.postgres.clone(), guild_id).await?;
+        let settings = EmojiSettings {
+            enabled: setting.emojis_enabled,
+            dedupe: setting.emojis_dedupe,
+        };
+
+        self.settings.lock().unwrap().insert(guild_id, settings);
+
+        Ok(settings)
+    }
+
+    pub fn forget_settings(&self, guild_id: GuildId) {
+        self.settings.lock().unwrap().remove(&guild_id);
+    }
+
+    /// Drop the events waiting in the buffer that match, for when they're being deleted anyway.
+    /// Once this returns, any write that was already underway has finished, so the database can
+    /// be deleted from too.
+    pub async fn discard(&self, matches: impl Fn(&EmojiEvent) -> bool) {
+        let _writing = self.writing.lock().await;
+
+        self.buffer.lock().unwrap().retain(|event| !matches(event));
+    }
+}
+
+pub async fn start(context: BaseContext) {
+    info!("starting emoji writer");
+
+    let mut interval = time::interval(FLUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(why) = context.emojis().flush().await {
+            error!("failed to flush emojis: {}", why);
+        }
+    }
+}